@@ -0,0 +1,83 @@
+#![feature(test)]
+#![allow(warnings)]
+
+extern crate test;
+extern crate rand;
+#[macro_use]
+extern crate spartacus;
+
+use test::{Bencher, black_box};
+use rand::{Rng, thread_rng};
+
+use spartacus::arena::{Arena, BoxArena};
+use spartacus::arena::vec_arena::VecArena;
+
+use spartacus::tree::TreeSeq;
+use spartacus::tree::rule::RevTreap;
+
+macro_rules! seq_insert_rand_bench {
+    ($name: ident, $n: expr, $seq: ty, $insert: ident, $remove: ident) => (
+        #[bench]
+        pub fn $name(b: &mut Bencher) {
+            let n: usize = $n;
+            let mut seq: $seq = Default::default();
+            // setup
+            let mut rng = thread_rng();
+
+            for i in 0..n {
+                seq.$insert(i, i);
+            }
+
+            // measure
+            b.iter(|| {
+                let i = rng.gen::<usize>() % n;
+                seq.$insert(i, i);
+                seq.$remove(i);
+            });
+            black_box(seq);
+        }
+    )
+}
+
+macro_rules! seq_get_rand_bench {
+    ($name: ident, $n: expr, $seq: ty, $insert: ident) => (
+        #[bench]
+        pub fn $name(b: &mut Bencher) {
+            let n: usize = $n;
+            let mut seq: $seq = Default::default();
+            // setup
+            let mut rng = thread_rng();
+
+            for i in 0..n {
+                seq.$insert(i, i);
+            }
+
+            // measure
+            b.iter(|| {
+                let t = seq.get(rng.gen::<usize>() % n);
+                black_box(t);
+            });
+        }
+    )
+}
+
+type StdVec = Vec<usize>;
+
+type BoxTreapSeq = TreeSeq<usize, RevTreap, BoxArena>;
+type VecTreapSeq = TreeSeq<usize, RevTreap, VecArena>;
+
+seq_insert_rand_bench!{insert_rand_100_std_vec,        100, StdVec,      insert,    remove}
+seq_insert_rand_bench!{insert_rand_100_box_treap_seq,  100, BoxTreapSeq, insert_at, remove_at}
+seq_insert_rand_bench!{insert_rand_100_vec_treap_seq,  100, VecTreapSeq, insert_at, remove_at}
+
+seq_insert_rand_bench!{insert_rand_10000_std_vec,       10000, StdVec,      insert,    remove}
+seq_insert_rand_bench!{insert_rand_10000_box_treap_seq, 10000, BoxTreapSeq, insert_at, remove_at}
+seq_insert_rand_bench!{insert_rand_10000_vec_treap_seq, 10000, VecTreapSeq, insert_at, remove_at}
+
+seq_insert_rand_bench!{insert_rand_1000000_std_vec,       1000000, StdVec,      insert,    remove}
+seq_insert_rand_bench!{insert_rand_1000000_box_treap_seq, 1000000, BoxTreapSeq, insert_at, remove_at}
+seq_insert_rand_bench!{insert_rand_1000000_vec_treap_seq, 1000000, VecTreapSeq, insert_at, remove_at}
+
+seq_get_rand_bench!{get_rand_10000_std_vec,       10000, StdVec,      insert}
+seq_get_rand_bench!{get_rand_10000_box_treap_seq, 10000, BoxTreapSeq, insert_at}
+seq_get_rand_bench!{get_rand_10000_vec_treap_seq, 10000, VecTreapSeq, insert_at}
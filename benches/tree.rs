@@ -12,7 +12,7 @@ use test::{Bencher, black_box};
 use rand::{Rng, thread_rng};
 
 use spartacus::arena::{Arena, BoxArena};
-use spartacus::arena::vec_arena::{VecArena, Boxed as VecBoxed};
+use spartacus::arena::vec_arena::VecArena;
 
 use spartacus::tree::TreeMap;
 use spartacus::tree::rule::{Noop, RevTreap};
@@ -131,10 +131,10 @@ macro_rules! map_find_seq_bench {
 
 type StdBTree = BTreeMap<usize, usize>;
 
-treemap!{BoxBst, usize, usize, Noop, BoxArena, Box, I1}
-treemap!{VecBst, usize, usize, Noop, VecArena, VecBoxed, I2}
-treemap!{BoxTreap, usize, usize, RevTreap, BoxArena, Box, I3}
-treemap!{VecTreap, usize, usize, RevTreap, VecArena, VecBoxed, I4}
+type BoxBst = TreeMap<usize, usize, Noop, BoxArena>;
+type VecBst = TreeMap<usize, usize, Noop, VecArena>;
+type BoxTreap = TreeMap<usize, usize, RevTreap, BoxArena>;
+type VecTreap = TreeMap<usize, usize, RevTreap, VecArena>;
 
 map_insert_rand_bench!{insert_rand_100_std_btree, 100, StdBTree}
 map_insert_rand_bench!{insert_rand_100_box_bst,   100, BoxBst}
@@ -149,7 +149,9 @@ map_insert_rand_bench!{insert_rand_10000_vec_bst,   10000, VecBst}
 map_insert_rand_bench!{insert_rand_10000_vec_treap, 10000, VecTreap}
 
 map_insert_rand_bench!{insert_rand_1000000_std_btree, 1000000, StdBTree}
+map_insert_rand_bench!{insert_rand_1000000_box_bst,   1000000, BoxBst}
 map_insert_rand_bench!{insert_rand_1000000_box_treap, 1000000, BoxTreap}
+map_insert_rand_bench!{insert_rand_1000000_vec_bst,   1000000, VecBst}
 map_insert_rand_bench!{insert_rand_1000000_vec_treap, 1000000, VecTreap}
 
 map_insert_seq_bench!{insert_seq_100_std_btree, 100, StdBTree}
@@ -164,6 +166,7 @@ map_insert_seq_bench!{insert_seq_10000_box_treap, 10000, BoxTreap}
 map_insert_seq_bench!{insert_seq_10000_vec_bst,   10000, VecBst}
 map_insert_seq_bench!{insert_seq_10000_vec_treap, 10000, VecTreap}
 
+// Sequential setup of `Noop` takes quadratic time at this size, so only random ones are measured
 map_insert_seq_bench!{insert_seq_1000000_std_btree, 1000000, StdBTree}
 map_insert_seq_bench!{insert_seq_1000000_box_treap, 1000000, BoxTreap}
 map_insert_seq_bench!{insert_seq_1000000_vec_treap, 1000000, VecTreap}
@@ -181,7 +184,9 @@ map_find_rand_bench!{find_rand_10000_vec_bst,   10000, VecBst}
 map_find_rand_bench!{find_rand_10000_vec_treap, 10000, VecTreap}
 
 map_find_rand_bench!{find_rand_1000000_std_btree, 1000000, StdBTree}
+map_find_rand_bench!{find_rand_1000000_box_bst,   1000000, BoxBst}
 map_find_rand_bench!{find_rand_1000000_box_treap, 1000000, BoxTreap}
+map_find_rand_bench!{find_rand_1000000_vec_bst,   1000000, VecBst}
 map_find_rand_bench!{find_rand_1000000_vec_treap, 1000000, VecTreap}
 
 map_find_seq_bench!{find_seq_100_std_btree, 100, StdBTree}
@@ -1,11 +1,63 @@
 use std::ops::{Deref, DerefMut};
 use std::marker::PhantomData;
+use std::mem::MaybeUninit;
 
 /// Abstracted typed allocator
 ///
 /// Similar to `std::heap::Alloc`, but more high-level and limited to single type
-pub trait Arena<T, B>: Default where B: Boxed<T> {
+pub trait Arena<T, B> where B: Boxed<T> {
     fn alloc(&self, value: T) -> B;
+
+    /// Allocate the value made by `f`, constructing it in place if the arena can.
+    ///
+    /// It saves moving large values from the stack, if the compiler elides the copy.
+    fn alloc_with<F: FnOnce() -> T>(&self, f: F) -> B {
+        self.alloc(f())
+    }
+
+    /// Allocate all values of the iterator, in order.
+    fn alloc_many<I: IntoIterator<Item=T>>(&self, values: I) -> Vec<B> {
+        values.into_iter().map(|value| self.alloc(value)).collect()
+    }
+
+    /// Drop all boxes, which may be faster than dropping them one by one.
+    fn free_many<I: IntoIterator<Item=B>>(&self, boxes: I) {
+        boxes.into_iter().for_each(drop);
+    }
+
+    /// Reserve space for at least `additional` more values, if the arena can.
+    fn reserve(&self, _additional: usize) {}
+
+    /// Whether boxes from the other arena can be used as if they're allocated from this arena.
+    fn shares_with(&self, _other: &Self) -> bool {
+        false
+    }
+
+    /// Occupancy of the slots, if the arena keeps track of them.
+    fn stats(&self) -> Option<ArenaStats> {
+        None
+    }
+}
+
+/// Arena which can be made for values of any type.
+///
+/// Recursive types like tree nodes can't name the arena of themselves,
+/// so they take the family instead. Arenas implement it regardless of their value type,
+/// so the arena type with its default value type can be used as the family, like `BoxArena`.
+pub trait ArenaFamily {
+    type Boxed<T>: Boxed<T>;
+    type Arena<T>: Arena<T, Self::Boxed<T>>;
+}
+
+/// Occupancy of an arena with numbered slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Number of slots ever used.
+    pub slots: usize,
+    /// Number of slots holding values.
+    pub occupied: usize,
+    /// Number of slots in the free list, waiting to be reused.
+    pub free: usize,
 }
 
 /// Abstracted allocated box
@@ -17,17 +69,43 @@ pub trait Boxed<T>: Deref<Target=T> + DerefMut {
 
     fn unbox(boxed: Self) -> T;
     fn to_unsafe(boxed: &mut Self) -> Self::Unsafe;
+
+    /// Whether the value is shared with other boxes, so it has no single owner.
+    fn is_shared(_boxed: &Self) -> bool {
+        false
+    }
+
+    /// Index of the slot holding the value, if the arena has numbered slots.
+    fn slot(_boxed: &Self) -> Option<usize> {
+        None
+    }
 }
 
 /// Conceptually a raw pointer to allocated box.
 pub trait UnsafeBoxed<T> {
+    /// # Safety
+    ///
+    /// The box this pointer was made from must still be alive,
+    /// and must not be mutably borrowed elsewhere.
     unsafe fn get(&self) -> &T;
+
+    /// # Safety
+    ///
+    /// The box this pointer was made from must still be alive,
+    /// and must not be borrowed elsewhere.
     unsafe fn get_mut(&mut self) -> &mut T;
+
+    /// Address of the value, which is compared without dereferencing it.
+    ///
+    /// The box may be gone already, so it may be dangling, or null if the arena knows so.
+    fn as_ptr(&self) -> *const T;
 }
 
 /// Simple typed allocator, just a wrapper around `Box`
 /// This can be useful for comparison.
-pub struct BoxArena<T>(PhantomData<*mut T>);
+///
+/// Its boxes coerce to `Box<dyn Trait>` as usual.
+pub struct BoxArena<T = ()>(PhantomData<*mut T>);
 
 impl<T> Default for BoxArena<T> {
     fn default() -> Self {
@@ -35,10 +113,36 @@ impl<T> Default for BoxArena<T> {
     }
 }
 
+impl<T> Clone for BoxArena<T> {
+    fn clone(&self) -> Self {
+        BoxArena(Default::default())
+    }
+}
+
+impl<T> BoxArena<T> {
+    /// Allocate a box whose value is written in place later, and taken by `Box::assume_init`.
+    pub fn alloc_uninit(&self) -> Box<MaybeUninit<T>> {
+        Box::new_uninit()
+    }
+}
+
 impl<T> Arena<T, Box<T>> for BoxArena<T> {
     fn alloc(&self, value: T) -> Box<T> {
         Box::new(value)
     }
+
+    fn alloc_with<F: FnOnce() -> T>(&self, f: F) -> Box<T> {
+        Box::write(Box::new_uninit(), f())
+    }
+
+    fn shares_with(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> ArenaFamily for BoxArena<T> {
+    type Boxed<U> = Box<U>;
+    type Arena<U> = BoxArena<U>;
 }
 
 impl<T> Boxed<T> for Box<T> {
@@ -61,4 +165,8 @@ impl<T> UnsafeBoxed<T> for *mut T {
     unsafe fn get_mut(&mut self) -> &mut T {
         &mut **self
     }
+
+    fn as_ptr(&self) -> *const T {
+        *self
+    }
 }
@@ -1,5 +1,8 @@
 
+#[allow(clippy::module_inception)]
 mod arena;
-pub use self::arena::{Arena, Boxed, UnsafeBoxed, BoxArena};
+pub use self::arena::{Arena, ArenaFamily, ArenaStats, Boxed, UnsafeBoxed, BoxArena};
 
 pub mod vec_arena;
+pub mod rc_arena;
+pub mod ref_arena;
@@ -0,0 +1,84 @@
+use std::rc::Rc;
+use std::ops::{Deref, DerefMut};
+use std::marker::PhantomData;
+
+use arena;
+
+/// Typed allocator whose boxes are reference-counted and copied on write.
+///
+/// Cloning a box only increments its reference count,
+/// and mutating a shared box clones the value before the mutation.
+pub struct RcArena<T>(PhantomData<*mut T>);
+
+pub struct Boxed<T>(Rc<T>);
+
+impl<T> RcArena<T> {
+    pub fn new() -> Self {
+        RcArena(PhantomData)
+    }
+}
+
+impl<T> Default for RcArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for RcArena<T> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> arena::Arena<T, Boxed<T>> for RcArena<T> {
+    fn alloc(&self, value: T) -> Boxed<T> {
+        Boxed(Rc::new(value))
+    }
+
+    fn shares_with(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Boxed<T> {
+    /// Check whether two boxes share the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Rc::ptr_eq(&this.0, &other.0)
+    }
+}
+
+impl<T> Clone for Boxed<T> {
+    fn clone(&self) -> Self {
+        Boxed(self.0.clone())
+    }
+}
+
+impl<T> Deref for Boxed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Clone> DerefMut for Boxed<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        Rc::make_mut(&mut self.0)
+    }
+}
+
+impl<T: Clone> arena::Boxed<T> for Boxed<T> {
+    type Unsafe = *mut T;
+
+    fn unbox(boxed: Self) -> T {
+        Rc::try_unwrap(boxed.0).unwrap_or_else(|shared| (*shared).clone())
+    }
+
+    fn to_unsafe(boxed: &mut Self) -> *mut T {
+        Rc::as_ptr(&boxed.0) as *mut T
+    }
+
+    fn is_shared(boxed: &Self) -> bool {
+        Rc::strong_count(&boxed.0) > 1
+    }
+}
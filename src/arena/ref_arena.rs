@@ -0,0 +1,266 @@
+use std::cell::{Cell, RefCell, UnsafeCell};
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use std::fmt;
+
+use arena;
+use super::vec_arena::locate;
+
+/// Typed allocator whose boxes borrow the arena, instead of sharing it by `Rc` like `VecArena`.
+///
+/// Boxes hold a pointer to their slot, so values are reached without touching the arena,
+/// and making or dropping boxes involves no reference count.
+/// Slots are still freed one by one, and reused by later allocations.
+///
+/// Boxes are made through the `ArenaRef` of `handle`, which is also the arena family,
+/// like `TreeMap<K, V, RevTreap, ArenaRef>` made by `TreeMap::new_in(arena.handle())`.
+///
+/// Slots are stored in chunks of doubling capacity, which never reallocate.
+pub struct RefArena<T = ()> {
+    storage: RefCell<Vec<Vec<UnsafeCell<Slot<T>>>>>,
+    len: Cell<usize>,
+    /// First slot of the free list, which is linked through the empty slots.
+    empty: Cell<Option<NonNull<Slot<T>>>>,
+    occupied: Cell<usize>,
+}
+
+/// Copyable handle of a `RefArena` borrowed for `'a`, which allocates boxes living as long.
+///
+/// It doesn't require `T: 'a` itself, so it can be named for any value type as the arena family.
+pub struct ArenaRef<'a, T = ()> {
+    /// Invariant over `T`, or values of shorter lifetime could be put into the arena.
+    arena: *mut RefArena<T>,
+    _marker: PhantomData<&'a ()>,
+}
+
+/// Box in a `RefArena`, which is a pointer to its slot and a handle of the arena.
+pub struct Boxed<'a, T> {
+    arena: ArenaRef<'a, T>,
+    slot: NonNull<Slot<T>>,
+}
+
+enum Slot<T> {
+    Data(ManuallyDrop<T>),
+    Empty(Option<NonNull<Slot<T>>>),
+}
+
+impl<T> Slot<T> {
+    fn data(&self) -> &T {
+        match *self {
+            Slot::Data(ref data) => data,
+            _ => panic!("This slot is not data"),
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut T {
+        match *self {
+            Slot::Data(ref mut data) => data,
+            _ => panic!("This slot is not data"),
+        }
+    }
+}
+
+impl<T> RefArena<T> {
+    pub fn new() -> Self {
+        RefArena {
+            storage: RefCell::new(vec![]),
+            len: Cell::new(0),
+            empty: Cell::new(None),
+            occupied: Cell::new(0),
+        }
+    }
+
+    /// Handle to allocate boxes which borrow this arena, so they can't outlive it:
+    ///
+    /// ```compile_fail
+    /// use spartacus::arena::Arena;
+    /// use spartacus::arena::ref_arena::RefArena;
+    ///
+    /// let boxed = {
+    ///     let arena = RefArena::new();
+    ///     arena.handle().alloc(1)
+    /// };
+    /// ```
+    pub fn handle(&self) -> ArenaRef<'_, T> {
+        ArenaRef {
+            arena: self as *const RefArena<T> as *mut RefArena<T>,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Append an empty slot out of the free list.
+    fn push_slot(&self) -> NonNull<Slot<T>> {
+        let mut storage = self.storage.borrow_mut();
+        let (chunk, _) = locate(self.len.get());
+        if chunk == storage.len() {
+            storage.push(Vec::with_capacity(1 << chunk));
+        }
+
+        let chunk = &mut storage[chunk];
+        chunk.push(UnsafeCell::new(Slot::Empty(None)));
+        self.len.set(self.len.get() + 1);
+
+        let slot = chunk.last().expect("slot should be just pushed").get();
+        unsafe { NonNull::new_unchecked(slot) }
+    }
+
+    fn alloc(&self, data: T) -> NonNull<Slot<T>> {
+        let slot = match self.empty.get() {
+            Some(slot) => {
+                match unsafe { &*slot.as_ptr() } {
+                    Slot::Empty(next) => self.empty.set(*next),
+                    Slot::Data(_) => panic!("This slot is not empty"),
+                }
+                slot
+            }
+            None => self.push_slot(),
+        };
+
+        unsafe { ptr::write(slot.as_ptr(), Slot::Data(ManuallyDrop::new(data))) }
+        self.occupied.set(self.occupied.get() + 1);
+
+        slot
+    }
+
+    fn free(&self, slot: NonNull<Slot<T>>) -> T {
+        let prev_empty = self.empty.replace(Some(slot));
+        self.occupied.set(self.occupied.get() - 1);
+
+        match unsafe { ptr::replace(slot.as_ptr(), Slot::Empty(prev_empty)) } {
+            Slot::Data(data) => ManuallyDrop::into_inner(data),
+            Slot::Empty(_) => panic!("This slot is not data"),
+        }
+    }
+
+    fn stats(&self) -> arena::ArenaStats {
+        let len = self.len.get();
+        let mut free = 0;
+        let mut empty = self.empty.get();
+
+        // A broken free list may have a cycle, which is cut at the number of slots
+        while let Some(slot) = empty {
+            if free == len {
+                break;
+            }
+            free += 1;
+
+            empty = match unsafe { &*slot.as_ptr() } {
+                Slot::Empty(next) => *next,
+                Slot::Data(_) => None,
+            };
+        }
+
+        arena::ArenaStats {
+            slots: len,
+            occupied: self.occupied.get(),
+            free,
+        }
+    }
+
+    fn reserve(&self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+
+        let mut storage = self.storage.borrow_mut();
+        let (last, _) = locate(self.len.get() + additional - 1);
+
+        while storage.len() <= last {
+            let chunk = storage.len();
+            storage.push(Vec::with_capacity(1 << chunk));
+        }
+    }
+}
+
+impl<T> Default for RefArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> ArenaRef<'a, T> {
+    fn get(&self) -> &'a RefArena<T> {
+        // Made from `&'a RefArena<T>` by `RefArena::handle`
+        unsafe { &*self.arena }
+    }
+}
+
+impl<'a, T> Clone for ArenaRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ArenaRef<'a, T> {}
+
+impl<'a, T> arena::Arena<T, Boxed<'a, T>> for ArenaRef<'a, T> {
+    fn alloc(&self, data: T) -> Boxed<'a, T> {
+        Boxed {
+            arena: *self,
+            slot: self.get().alloc(data),
+        }
+    }
+
+    fn reserve(&self, additional: usize) {
+        self.get().reserve(additional);
+    }
+
+    fn shares_with(&self, other: &Self) -> bool {
+        self.arena == other.arena
+    }
+
+    fn stats(&self) -> Option<arena::ArenaStats> {
+        Some(self.get().stats())
+    }
+}
+
+impl<'a, T> arena::ArenaFamily for ArenaRef<'a, T> {
+    type Boxed<U> = Boxed<'a, U>;
+    type Arena<U> = ArenaRef<'a, U>;
+}
+
+impl<'a, T> Deref for Boxed<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { (*self.slot.as_ptr()).data() }
+    }
+}
+
+impl<'a, T> DerefMut for Boxed<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { (*self.slot.as_ptr()).data_mut() }
+    }
+}
+
+impl<'a, T> arena::Boxed<T> for Boxed<'a, T> {
+    type Unsafe = *mut T;
+
+    fn unbox(boxed: Self) -> T {
+        let arena = boxed.arena.get();
+        let slot = boxed.slot;
+        mem::forget(boxed);
+
+        arena.free(slot)
+    }
+
+    fn to_unsafe(boxed: &mut Self) -> *mut T {
+        &mut **boxed as *mut T
+    }
+}
+
+impl<'a, T> Drop for Boxed<'a, T> {
+    fn drop(&mut self) {
+        let data = self.arena.get().free(self.slot);
+        // The slot is freed before dropping the data, which may have boxes in this arena
+        drop(data);
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for Boxed<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -1,47 +1,334 @@
 use std::rc::Rc;
 use std::cell::{RefCell, RefMut, UnsafeCell};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
 use std::ops::{Deref, DerefMut};
-use std::{usize, mem, ptr};
+use std::{borrow, fmt, mem, ptr};
+use std::error::Error;
 
 use arena;
 
-pub struct VecArena<T>(Rc<RefCell<ArenaData<T>>>);
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::ser::{SerializeSeq, Error as SerError};
+
+pub struct VecArena<T = ()>(Rc<RefCell<ArenaData<T>>>);
+
+const STALE: &str = "box is used after its arena is cleared";
 
 pub struct Boxed<T> {
     arena: VecArena<T>,
     index: usize,
 }
 
+/// Box in a `VecArena` whose value type is erased into `U`, usually a trait object.
+///
+/// It's made by `Boxed::unsize`, and still drops the value and frees its slot when dropped.
+/// Boxes of different types from different arenas can be put together as `DynBoxed<dyn Trait>`.
+///
+/// It lives no longer than `'a`, which the erased type outlives, since the value is dropped with it.
+pub struct DynBoxed<'a, U: ?Sized + 'a> {
+    data: *mut U,
+    index: usize,
+    /// `Rc` of the arena data, whose type is known only to `free` and `resolve`.
+    arena: *const (),
+    free: unsafe fn(*const (), usize),
+    resolve: unsafe fn(*const (), usize) -> Option<usize>,
+    _marker: PhantomData<(&'a (), U)>,
+}
+
 pub struct UnsafeBoxed<T> {
     arena: VecArena<T>,
     index: usize,
 }
 
+/// Slot taken by `VecArena::alloc_uninit`, whose value is not initialized yet.
+///
+/// It derefs to `MaybeUninit<T>` to be written in place, and becomes `Boxed<T>`
+/// by `init` or `assume_init`. If dropped before that, the slot is freed without dropping anything.
+pub struct Uninit<T> {
+    arena: VecArena<T>,
+    index: usize,
+}
+
+/// Arena of `VecArena::scope`, branded with the lifetime of the scope.
+///
+/// The brand is invariant, so boxes can't be mixed up between nested scopes either.
+pub struct Scope<'s, T> {
+    arena: VecArena<T>,
+    _brand: PhantomData<fn(&'s ()) -> &'s ()>,
+}
+
+/// Box in a `Scope`, which can't outlive the scope.
+pub struct ScopedBoxed<'s, T> {
+    boxed: Boxed<T>,
+    _brand: PhantomData<fn(&'s ()) -> &'s ()>,
+}
+
+/// Slots are stored in chunks of doubling capacity, which never reallocate.
+/// So values never move while they're alive, even if the arena grows.
 struct ArenaData<T> {
-    storage: Vec<UnsafeCell<Slot<T>>>,
+    storage: Vec<Vec<UnsafeCell<Slot<T>>>>,
+    len: usize,
     empty: usize,
+    /// Number of slots holding values.
+    occupied: usize,
+    /// Number of slots taken by `Uninit`, which hold no values yet.
+    uninit: usize,
+    /// Handle index of the first slot, which grows past all handles made before each `clear`.
+    base: usize,
+    /// Slots restored by deserialization, whose data no `Boxed` owns yet.
+    #[cfg(feature = "serde")]
+    restored: Vec<bool>,
 }
 
+/// Broken invariant of a `VecArena`, found by `VecArena::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The free list comes back to the slot.
+    FreeListCycle { index: usize },
+    /// The free list points a slot beyond the storage.
+    FreeListOutOfBounds { index: usize },
+    /// The free list points a slot holding data.
+    FreeSlotOccupied { index: usize },
+    /// Numbers of occupied and free slots don't add up to the number of slots,
+    /// except the ones taken by `Uninit`.
+    CountMismatch { occupied: usize, free: usize, len: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::FreeListCycle { index } =>
+                write!(f, "free list has a cycle at slot {}", index),
+            Violation::FreeListOutOfBounds { index } =>
+                write!(f, "free list points slot {} out of bounds", index),
+            Violation::FreeSlotOccupied { index } =>
+                write!(f, "free list points slot {} holding data", index),
+            Violation::CountMismatch { occupied, free, len } =>
+                write!(f, "{} occupied and {} free slots don't add up to {}", occupied, free, len),
+        }
+    }
+}
+
+impl Error for Violation {}
+
 trait SlotPtrExt<T> {
-    fn to_ref<'a, U>(self, life: &'a U) -> &'a T;
-    fn to_mut<'a, U>(self, life: &'a mut U) -> &'a mut T;
-    fn set_data(self, data: T) -> usize;
+    fn data_ref<U>(self, life: &U) -> &T;
+    fn data_mut<U>(self, life: &mut U) -> &mut T;
+    /// Pointer to the data of the slot, which may not be initialized.
+    fn data_ptr(self) -> *mut T;
+    /// Mark the empty slot as holding data, leaving it uninitialized.
+    /// Returns the next slot in the free list.
+    fn occupy(self) -> usize;
     fn set_empty(self, empty: usize) -> T;
+    /// Point the next slot in the free list from the slot, forgetting the data it may hold.
+    fn link_empty(self, empty: usize);
+    /// Next slot in the free list, or `None` if the slot is known to hold data.
+    fn next_empty(self) -> Option<usize>;
 }
 
 impl<T> VecArena<T> {
     pub fn new() -> Self {
         VecArena(Rc::new(RefCell::new(ArenaData {
             storage: vec![],
+            len: 0,
             empty: usize::MAX,
+            occupied: 0,
+            uninit: 0,
+            base: 0,
+            #[cfg(feature = "serde")]
+            restored: vec![],
         })))
     }
 
-    fn get(&self) -> RefMut<ArenaData<T>> {
+    /// Take a slot, whose value is written in place later through the `Uninit`.
+    pub fn alloc_uninit(&self) -> Uninit<T> {
+        let mut data = self.get();
+        let index = data.take_empty();
+        data.uninit += 1;
+
+        Uninit {
+            arena: self.clone(),
+            index: data.handle(index),
+        }
+    }
+
+    /// Take the handle of the slot restored by deserialization.
+    ///
+    /// Returns `None` if the slot is not restored, or its handle is already taken.
+    /// Indices of the handles can be saved before serialization by `Boxed::slot`.
+    #[cfg(feature = "serde")]
+    pub fn claim(&self, index: usize) -> Option<Boxed<T>> {
+        let mut data = self.get();
+
+        match data.restored.get_mut(index) {
+            Some(restored) if *restored => *restored = false,
+            _ => return None,
+        }
+
+        Some(Boxed {
+            arena: self.clone(),
+            index: data.handle(index),
+        })
+    }
+
+    /// Check the free list and the slot counts, returning the first violation found.
+    ///
+    /// It takes `O(n)` for `n` slots.
+    pub fn validate(&self) -> Result<(), Violation> {
+        self.get().validate()
+    }
+
+    /// Drop all values and free all slots at once, keeping the storage to be reused.
+    ///
+    /// Boxes made before are invalidated: dereferencing them panics, and dropping them does nothing.
+    /// Values are dropped after the arena is released, as they may have boxes in it.
+    /// `VecArena::scope` calls it when the scope ends, which needs no `unsafe`.
+    ///
+    /// # Safety
+    ///
+    /// No reference to a value in the arena may be alive across the call,
+    /// since references from `Deref` of `Boxed`, `DynBoxed` or `Uninit` borrow the box, not the arena.
+    /// For example, this is undefined behavior:
+    ///
+    /// ```no_run
+    /// use spartacus::arena::Arena;
+    /// use spartacus::arena::vec_arena::VecArena;
+    ///
+    /// let arena = VecArena::new();
+    /// let boxed = arena.alloc(String::from("freed"));
+    /// let value: &String = &boxed;
+    /// unsafe { arena.clear() }
+    /// println!("{}", value);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a slot taken by `alloc_uninit` is not initialized yet.
+    pub unsafe fn clear(&self) {
+        let (mut storage, occupied) = {
+            let mut data = self.get();
+            assert_eq!(data.uninit, 0, "arena is cleared while its slots are being initialized");
+
+            let occupied = if mem::needs_drop::<T>() { data.occupancy() } else { vec![] };
+            data.base = data.base.checked_add(data.len).expect("handle indices overflow");
+            data.len = 0;
+            data.empty = usize::MAX;
+            data.occupied = 0;
+            #[cfg(feature = "serde")]
+            data.restored.clear();
+
+            (mem::take(&mut data.storage), occupied)
+        };
+
+        for (index, _) in occupied.iter().enumerate().filter(|&(_, &occupied)| occupied) {
+            let (chunk, offset) = locate(index);
+            drop(storage[chunk][offset].get().set_empty(usize::MAX));
+        }
+
+        for chunk in &mut storage {
+            chunk.clear();
+        }
+
+        let mut data = self.get();
+
+        // Dropped values may have allocated new chunks already
+        if data.storage.is_empty() {
+            data.storage = storage;
+        }
+    }
+
+    /// Run `f` with a new arena whose boxes can't escape `f`, and drop them all at once after it.
+    ///
+    /// Boxes are branded with the lifetime of the scope, which `f` can't return.
+    /// They are still freed one by one when dropped in the scope, and the rest,
+    /// like the ones held by values in the arena, are dropped by `clear` when `f` returns or panics.
+    /// Boxes left in values are invalidated then, so dereferencing them in `Drop` panics.
+    ///
+    /// ```compile_fail
+    /// use spartacus::arena::vec_arena::VecArena;
+    ///
+    /// let escaped = VecArena::scope(|scope| scope.alloc(1));
+    /// ```
+    pub fn scope<F, R>(f: F) -> R where
+        F: for<'s> FnOnce(&Scope<'s, T>) -> R
+    {
+        let scope = Scope {
+            arena: VecArena::new(),
+            _brand: PhantomData,
+        };
+
+        f(&scope)
+    }
+
+    fn get(&self) -> RefMut<'_, ArenaData<T>> {
         self.0.borrow_mut()
     }
 }
 
+impl<'s, T> Scope<'s, T> {
+    pub fn alloc(&self, value: T) -> ScopedBoxed<'s, T> {
+        ScopedBoxed {
+            boxed: arena::Arena::alloc(&self.arena, value),
+            _brand: PhantomData,
+        }
+    }
+
+    /// Write the value made by `f` directly into the slot, like `VecArena::alloc_with`.
+    pub fn alloc_with<F: FnOnce() -> T>(&self, f: F) -> ScopedBoxed<'s, T> {
+        ScopedBoxed {
+            boxed: arena::Arena::alloc_with(&self.arena, f),
+            _brand: PhantomData,
+        }
+    }
+
+    pub fn reserve(&self, additional: usize) {
+        arena::Arena::reserve(&self.arena, additional);
+    }
+
+    pub fn stats(&self) -> arena::ArenaStats {
+        self.arena.get().stats()
+    }
+}
+
+impl<'s, T> Drop for Scope<'s, T> {
+    fn drop(&mut self) {
+        // Boxes and references to values can't outlive `f`, which has returned or panicked
+        unsafe { self.arena.clear() }
+    }
+}
+
+impl<'s, T> ScopedBoxed<'s, T> {
+    /// Move the value out of the arena, freeing its slot.
+    pub fn unbox(boxed: Self) -> T {
+        arena::Boxed::unbox(boxed.boxed)
+    }
+}
+
+impl<'s, T> Deref for ScopedBoxed<'s, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.boxed
+    }
+}
+
+impl<'s, T> DerefMut for ScopedBoxed<'s, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.boxed
+    }
+}
+
+impl<'s, T: fmt::Debug> fmt::Debug for ScopedBoxed<'s, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
 impl<T> Default for VecArena<T> {
     fn default() -> Self {
         Self::new()
@@ -56,28 +343,128 @@ impl<T> Clone for VecArena<T> {
 
 impl<T> arena::Arena<T, Boxed<T>> for VecArena<T> {
     fn alloc(&self, data: T) -> Boxed<T> {
-        let index = self.get().alloc(data);
+        let index = {
+            let mut arena = self.get();
+            let index = arena.alloc(data);
+            arena.handle(index)
+        };
 
         Boxed {
             arena: self.clone(),
             index,
         }
     }
+
+    /// Write the value made by `f` directly into the slot.
+    ///
+    /// The arena is not borrowed while `f` runs, so it may allocate in this arena.
+    fn alloc_with<F: FnOnce() -> T>(&self, f: F) -> Boxed<T> {
+        let mut slot = self.alloc_uninit();
+
+        unsafe {
+            // The slot is freed by `Uninit` if `f` panics
+            ptr::write(slot.as_mut_ptr(), f());
+            slot.assume_init()
+        }
+    }
+
+    /// Allocate all values under a single borrow of the arena.
+    ///
+    /// Free slots are reused first, and the rest are appended next to each other.
+    /// So the slots are contiguous only if the free list is empty, like in a fresh arena.
+    /// Filling holes first keeps the arena from growing while it has room.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the iterator uses this arena, which is borrowed while iterating.
+    fn alloc_many<I: IntoIterator<Item=T>>(&self, values: I) -> Vec<Boxed<T>> {
+        let values = values.into_iter();
+        let mut boxes = Vec::with_capacity(values.size_hint().0);
+        // Declared after `boxes`, so it's released first if the iterator panics
+        let mut data = self.get();
+        data.reserve(values.size_hint().0);
+
+        for value in values {
+            let index = data.alloc(value);
+
+            boxes.push(Boxed {
+                arena: self.clone(),
+                index: data.handle(index),
+            });
+        }
+
+        boxes
+    }
+
+    /// Free slots of all boxes under a single borrow of the arena.
+    ///
+    /// Values are dropped after the arena is released, as they may have boxes in it.
+    /// Boxes from other arenas are dropped as usual, and the ones invalidated by `clear` are ignored.
+    fn free_many<I: IntoIterator<Item=Boxed<T>>>(&self, boxes: I) {
+        let boxes: Vec<_> = boxes.into_iter().collect();
+        let mut values = Vec::new();
+        let mut others = Vec::new();
+
+        {
+            let mut data = self.get();
+
+            for boxed in boxes {
+                if !Rc::ptr_eq(&boxed.arena.0, &self.0) {
+                    others.push(boxed);
+                    continue;
+                }
+
+                let index = boxed.index;
+                drop(unsafe { ptr::read(&boxed.arena) });
+                mem::forget(boxed);
+
+                let value = match data.resolve(index) {
+                    Some(index) => data.free(index),
+                    None => continue,
+                };
+
+                // Values without drop glue can't touch the arena
+                if mem::needs_drop::<T>() {
+                    values.push(value);
+                }
+            }
+        }
+
+        drop(values);
+        drop(others);
+    }
+
+    fn reserve(&self, additional: usize) {
+        self.get().reserve(additional);
+    }
+
+    fn shares_with(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn stats(&self) -> Option<arena::ArenaStats> {
+        Some(self.get().stats())
+    }
+}
+
+impl<T> arena::ArenaFamily for VecArena<T> {
+    type Boxed<U> = Boxed<U>;
+    type Arena<U> = VecArena<U>;
 }
 
 impl<T> Deref for Boxed<T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        let slot = self.arena.get().slot(self.index);
-        slot.to_ref(self)
+        let slot = self.arena.get().handle_slot(self.index);
+        slot.data_ref(self)
     }
 }
 
 impl<T> DerefMut for Boxed<T> {
     fn deref_mut(&mut self) -> &mut T {
-        let slot = self.arena.get().slot(self.index);
-        slot.to_mut(self)
+        let slot = self.arena.get().handle_slot(self.index);
+        slot.data_mut(self)
     }
 }
 
@@ -85,13 +472,13 @@ impl<T> arena::Boxed<T> for Boxed<T> {
     type Unsafe = UnsafeBoxed<T>;
 
     fn unbox(boxed: Self) -> T {
-        let data = {
-            let mut arena = boxed.arena.get();
-            arena.free(boxed.index)
-        };
+        let arena = unsafe { ptr::read(&boxed.arena) };
+        let index = boxed.index;
         mem::forget(boxed);
 
-        data
+        let mut data = arena.get();
+        let index = data.resolve(index).expect(STALE);
+        data.free(index)
     }
 
     fn to_unsafe(boxed: &mut Self) -> Self::Unsafe {
@@ -100,52 +487,535 @@ impl<T> arena::Boxed<T> for Boxed<T> {
             index: boxed.index,
         }
     }
+
+    /// Returns `None` if the box is invalidated by `VecArena::clear`.
+    fn slot(boxed: &Self) -> Option<usize> {
+        boxed.arena.get().resolve(boxed.index)
+    }
+}
+
+/// Boxes invalidated by `VecArena::clear` are dropped without touching the arena.
+impl<T> Drop for Boxed<T> {
+    fn drop(&mut self) {
+        let data = {
+            let mut arena = self.arena.get();
+
+            match arena.resolve(self.index) {
+                Some(index) => arena.free(index),
+                None => return,
+            }
+        };
+        // The arena should not be borrowed while dropping the data, which may have boxes in it
+        drop(data);
+    }
+}
+
+impl<T> Boxed<T> {
+    /// Erase the type of the value into `U`, like `boxed.unsize(|v| v as &mut dyn Trait)`.
+    ///
+    /// The value stays in its slot, which never moves while the value is alive.
+    /// Borrows in the value bound the lifetime of the box, even if `U` doesn't name them:
+    ///
+    /// ```compile_fail
+    /// use spartacus::arena::Arena;
+    /// use spartacus::arena::vec_arena::{VecArena, DynBoxed};
+    ///
+    /// let arena = VecArena::new();
+    /// let erased: DynBoxed<'static, [u8]>;
+    /// {
+    ///     let name = String::from("dangling");
+    ///     let boxed = arena.alloc((&name, [0u8; 4]));
+    ///     erased = boxed.unsize(|value| &mut value.1 as &mut [u8]);
+    /// }
+    /// drop(erased);
+    /// ```
+    pub fn unsize<'a, U: ?Sized + 'a, F>(mut self, f: F) -> DynBoxed<'a, U> where
+        T: 'a,
+        F: FnOnce(&mut T) -> &mut U
+    {
+        let data: *mut U = f(&mut self);
+        let index = self.index;
+        let arena = unsafe { ptr::read(&self.arena) };
+        mem::forget(self);
+
+        DynBoxed {
+            data,
+            index,
+            arena: Rc::into_raw(arena.0) as *const (),
+            free: free_erased::<T>,
+            resolve: resolve_erased::<T>,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Drop the value of the slot and the `Rc` of the arena, which are erased by `Boxed::unsize`.
+unsafe fn free_erased<T>(arena: *const (), index: usize) {
+    let arena = VecArena(Rc::from_raw(arena as *const RefCell<ArenaData<T>>));
+    let data = {
+        let mut data = arena.get();
+
+        match data.resolve(index) {
+            Some(index) => data.free(index),
+            None => return,
+        }
+    };
+    // The arena should not be borrowed while dropping the data, which may have boxes in it
+    drop(data);
+}
+
+/// Slot of the handle index in the arena erased by `Boxed::unsize`.
+unsafe fn resolve_erased<T>(arena: *const (), index: usize) -> Option<usize> {
+    let arena = &*(arena as *const RefCell<ArenaData<T>>);
+    let slot = arena.borrow_mut().resolve(index);
+    slot
+}
+
+impl<'a, U: ?Sized + 'a> DynBoxed<'a, U> {
+    /// Index of the slot holding the value in its arena,
+    /// or `None` if the box is invalidated by `VecArena::clear`.
+    pub fn slot(&self) -> Option<usize> {
+        unsafe { (self.resolve)(self.arena, self.index) }
+    }
+}
+
+/// # Panics
+///
+/// Panics if the box is invalidated by `VecArena::clear`,
+/// as its value is already dropped.
+impl<'a, U: ?Sized + 'a> Deref for DynBoxed<'a, U> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        self.slot().expect(STALE);
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, U: ?Sized + 'a> DerefMut for DynBoxed<'a, U> {
+    fn deref_mut(&mut self) -> &mut U {
+        self.slot().expect(STALE);
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, U: ?Sized + 'a> Drop for DynBoxed<'a, U> {
+    fn drop(&mut self) {
+        unsafe { (self.free)(self.arena, self.index) }
+    }
+}
+
+impl<'a, U: ?Sized + fmt::Debug + 'a> fmt::Debug for DynBoxed<'a, U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, U: ?Sized + fmt::Display + 'a> fmt::Display for DynBoxed<'a, U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// Allocate the clone of the data in a new slot of the same arena.
+impl<T: Clone> Clone for Boxed<T> {
+    fn clone(&self) -> Self {
+        let data = (**self).clone();
+        arena::Arena::alloc(&self.arena, data)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Boxed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Boxed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// Format the address of the data, like `Box`.
+impl<T> fmt::Pointer for Boxed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ptr: *const T = &**self;
+        fmt::Pointer::fmt(&ptr, f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Boxed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for Boxed<T> {}
+
+impl<T: PartialOrd> PartialOrd for Boxed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord> Ord for Boxed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash> Hash for Boxed<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T> borrow::Borrow<T> for Boxed<T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T> borrow::BorrowMut<T> for Boxed<T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> AsRef<T> for Boxed<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T> AsMut<T> for Boxed<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> Uninit<T> {
+    /// Index of the slot taken.
+    pub fn slot(&self) -> usize {
+        self.arena.get().resolve(self.index).expect(STALE)
+    }
+
+    /// Write the value and take the box of it.
+    pub fn init(mut self, value: T) -> Boxed<T> {
+        unsafe {
+            ptr::write(self.as_mut_ptr(), value);
+            self.assume_init()
+        }
+    }
+
+    /// Take the box of the value written in place.
+    ///
+    /// # Safety
+    ///
+    /// The value should be fully initialized, like `MaybeUninit::assume_init`.
+    pub unsafe fn assume_init(self) -> Boxed<T> {
+        let arena = ptr::read(&self.arena);
+        let index = self.index;
+        mem::forget(self);
+
+        {
+            let mut data = arena.get();
+            data.uninit -= 1;
+            data.occupied += 1;
+        }
+
+        Boxed {
+            arena,
+            index,
+        }
+    }
+}
+
+impl<T> Deref for Uninit<T> {
+    type Target = MaybeUninit<T>;
+
+    fn deref(&self) -> &MaybeUninit<T> {
+        let data = self.arena.get().handle_slot(self.index).data_ptr();
+        // `MaybeUninit<T>` has the same layout as `T`
+        unsafe { &*(data as *const MaybeUninit<T>) }
+    }
+}
+
+impl<T> DerefMut for Uninit<T> {
+    fn deref_mut(&mut self) -> &mut MaybeUninit<T> {
+        let data = self.arena.get().handle_slot(self.index).data_ptr();
+        unsafe { &mut *(data as *mut MaybeUninit<T>) }
+    }
+}
+
+impl<T> Drop for Uninit<T> {
+    fn drop(&mut self) {
+        let mut data = self.arena.get();
+        let index = data.resolve(self.index).expect(STALE);
+        data.release(index);
+    }
 }
 
 impl<T> arena::UnsafeBoxed<T> for UnsafeBoxed<T> {
     unsafe fn get(&self) -> &T {
-        let slot = self.arena.get().slot(self.index);
-        slot.to_ref(self)
+        let slot = self.arena.get().handle_slot(self.index);
+        slot.data_ref(self)
     }
 
     unsafe fn get_mut(&mut self) -> &mut T {
-        let slot = self.arena.get().slot(self.index);
-        slot.to_mut(self)
+        let slot = self.arena.get().handle_slot(self.index);
+        slot.data_mut(self)
+    }
+
+    fn as_ptr(&self) -> *const T {
+        let data = self.arena.get();
+
+        match data.resolve(self.index) {
+            Some(index) if index < data.len => data.slot(index).data_ptr(),
+            _ => ptr::null(),
+        }
     }
 }
 
+/// Chunk number and offset within the chunk of the slot,
+/// where chunk `n` holds `2^n` slots.
+pub(super) fn locate(index: usize) -> (usize, usize) {
+    let pos = index + 1;
+    let chunk = (mem::size_of::<usize>() * 8 - 1) - pos.leading_zeros() as usize;
+
+    (chunk, pos - (1 << chunk))
+}
+
 impl<T> ArenaData<T> {
     fn slot(&self, index: usize) -> *mut Slot<T> {
-        self.storage[index].get()
+        let (chunk, offset) = locate(index);
+        self.storage[chunk][offset].get()
     }
 
-    fn alloc(&mut self, data: T) -> usize {
-        if mem::size_of::<T>() == 0 {
-            return 0;
+    /// Handle index of the slot, which is never reused after `clear`.
+    fn handle(&self, index: usize) -> usize {
+        self.base + index
+    }
+
+    /// Slot of the handle index, or `None` if the handle is made before the last `clear`.
+    fn resolve(&self, index: usize) -> Option<usize> {
+        index.checked_sub(self.base)
+    }
+
+    fn handle_slot(&self, index: usize) -> *mut Slot<T> {
+        self.slot(self.resolve(index).expect(STALE))
+    }
+
+    /// Append an empty slot out of the free list.
+    fn push_slot(&mut self) -> usize {
+        let (chunk, _) = locate(self.len);
+        if chunk == self.storage.len() {
+            self.storage.push(Vec::with_capacity(1 << chunk));
         }
 
+        self.storage[chunk].push(Slot::default().into());
+        self.len += 1;
+
+        self.len - 1
+    }
+
+    /// Take the first slot of the free list, which is marked as holding uninitialized data.
+    fn take_empty(&mut self) -> usize {
         if self.empty == usize::MAX {
-            self.empty = self.storage.len();
-            self.storage.push(Slot::default().into());
+            self.empty = self.push_slot();
         }
 
         let index = self.empty;
-        self.empty = self.slot(index).set_data(data);
+        self.empty = self.slot(index).occupy();
+
+        index
+    }
+
+    fn alloc(&mut self, data: T) -> usize {
+        let index = self.take_empty();
+        unsafe { ptr::write(self.slot(index).data_ptr(), data) }
+        self.occupied += 1;
 
         index
     }
 
+    /// Put the slot taken by `Uninit` back to the free list.
+    fn release(&mut self, index: usize) {
+        self.slot(index).link_empty(self.empty);
+        self.empty = index;
+        self.uninit -= 1;
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+
+        let (last, _) = locate(self.len + additional - 1);
+
+        while self.storage.len() <= last {
+            let chunk = self.storage.len();
+            self.storage.push(Vec::with_capacity(1 << chunk));
+        }
+    }
+
     fn free(&mut self, index: usize) -> T {
         let prev_empty = self.empty;
         self.empty = index;
+        self.occupied -= 1;
 
         self.slot(index).set_empty(prev_empty)
     }
+
+    /// Whether each slot holds data, found by walking the free list.
+    fn occupancy(&self) -> Vec<bool> {
+        let mut occupied = vec![true; self.len];
+        let mut index = self.empty;
+
+        while index < self.len && occupied[index] {
+            occupied[index] = false;
+
+            match self.slot(index).next_empty() {
+                Some(next) => index = next,
+                None => break,
+            }
+        }
+
+        occupied
+    }
+
+    /// Rebuild the storage keeping the index of each slot.
+    ///
+    /// Empty slots are linked to the free list in ascending order.
+    #[cfg(feature = "serde")]
+    fn restore(&mut self, slots: Vec<Option<T>>) {
+        self.reserve(slots.len());
+
+        for slot in slots {
+            let index = self.push_slot();
+            let restored = slot.is_some();
+
+            if let Some(data) = slot {
+                self.slot(index).occupy();
+                unsafe { ptr::write(self.slot(index).data_ptr(), data) }
+                self.occupied += 1;
+            }
+
+            self.restored.push(restored);
+        }
+
+        for index in (0..self.len).rev() {
+            if !self.restored[index] {
+                self.slot(index).link_empty(self.empty);
+                self.empty = index;
+            }
+        }
+    }
+
+    fn stats(&self) -> arena::ArenaStats {
+        let mut free = 0;
+        let mut index = self.empty;
+
+        // A broken free list may have a cycle, which is cut at the number of slots
+        while index < self.len && free < self.len {
+            free += 1;
+
+            match self.slot(index).next_empty() {
+                Some(next) => index = next,
+                None => break,
+            }
+        }
+
+        arena::ArenaStats {
+            slots: self.len,
+            occupied: self.occupied,
+            free,
+        }
+    }
+
+    fn validate(&self) -> Result<(), Violation> {
+        let mut visited = vec![false; self.len];
+        let mut free = 0;
+        let mut index = self.empty;
+
+        while index != usize::MAX {
+            if index >= self.len {
+                return Err(Violation::FreeListOutOfBounds { index });
+            }
+
+            if visited[index] {
+                return Err(Violation::FreeListCycle { index });
+            }
+
+            visited[index] = true;
+            free += 1;
+            index = self.slot(index).next_empty().ok_or(Violation::FreeSlotOccupied { index })?;
+        }
+
+        if self.occupied + self.uninit + free != self.len {
+            return Err(Violation::CountMismatch { occupied: self.occupied, free, len: self.len });
+        }
+
+        Ok(())
+    }
+}
+
+/// Drop the data of the restored slots which are never claimed.
+#[cfg(feature = "serde")]
+impl<T> Drop for ArenaData<T> {
+    fn drop(&mut self) {
+        for index in 0..self.restored.len() {
+            if self.restored[index] {
+                drop(self.slot(index).set_empty(usize::MAX));
+            }
+        }
+    }
+}
+
+/// Serialize slots in order, where empty ones are `None`, so indices of the slots are kept.
+#[cfg(feature = "serde")]
+impl<T: Serialize> Serialize for VecArena<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let slots: Vec<_> = {
+            let data = self.get();
+
+            if data.uninit > 0 {
+                return Err(S::Error::custom("arena has uninitialized slots"));
+            }
+
+            let occupied = data.occupancy();
+            (0..data.len).map(|index| if occupied[index] { Some(data.slot(index)) } else { None }).collect()
+        };
+
+        // The arena should not be borrowed while serializing the data, which may have boxes in it
+        let mut seq = serializer.serialize_seq(Some(slots.len()))?;
+
+        for slot in slots {
+            seq.serialize_element(&slot.map(|slot| slot.data_ref(self)))?;
+        }
+
+        seq.end()
+    }
 }
 
+/// Restore slots at their indices. Their handles can be taken by `VecArena::claim`.
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for VecArena<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let slots = Vec::<Option<T>>::deserialize(deserializer)?;
+        let arena = VecArena::new();
+        arena.get().restore(slots);
+
+        Ok(arena)
+    }
+}
+
+/// Data is kept in `MaybeUninit`, so slots taken by `Uninit` can be marked as holding data
+/// before it's written.
 #[cfg(not(feature = "unions"))]
 enum Slot<T> {
-    Data(T),
+    Data(MaybeUninit<T>),
     Empty(usize),
 }
 
@@ -158,33 +1028,38 @@ impl<T> Default for Slot<T> {
 
 #[cfg(not(feature = "unions"))]
 impl<T> SlotPtrExt<T> for *mut Slot<T> {
-    fn to_ref<'a, U>(self, _life: &'a U) -> &'a T {
+    fn data_ref<U>(self, _life: &U) -> &T {
         unsafe {
             match *self {
-                Slot::Data(ref data) => {
-                    mem::transmute::<&T, &'a T>(data)
-                }
+                Slot::Data(ref data) => &*data.as_ptr(),
                 _ => panic!("This slot is not data"),
             }
         }
     }
 
-    fn to_mut<'a, U>(self, _life: &'a mut U) -> &'a mut T {
+    fn data_mut<U>(self, _life: &mut U) -> &mut T {
         unsafe {
             match *self {
-                Slot::Data(ref mut data) => {
-                    mem::transmute::<&mut T, &'a mut T>(data)
-                }
+                Slot::Data(ref mut data) => &mut *data.as_mut_ptr(),
                 _ => panic!("This slot is not data"),
             }
         }
     }
 
-    fn set_data(self, data: T) -> usize {
+    fn data_ptr(self) -> *mut T {
         unsafe {
-            match ptr::read(self) {
+            match *self {
+                Slot::Data(ref mut data) => data.as_mut_ptr(),
+                _ => panic!("This slot is not data"),
+            }
+        }
+    }
+
+    fn occupy(self) -> usize {
+        unsafe {
+            match *self {
                 Slot::Empty(empty) => {
-                    ptr::write(self, Slot::Data(data));
+                    ptr::write(self, Slot::Data(MaybeUninit::uninit()));
                     empty
                 }
                 _ => panic!("This slot is not empty"),
@@ -197,12 +1072,27 @@ impl<T> SlotPtrExt<T> for *mut Slot<T> {
             match ptr::read(self) {
                 Slot::Data(data) => {
                     ptr::write(self, Slot::Empty(empty));
-                    data
+                    data.assume_init()
                 }
                 _ => panic!("This slot is not data"),
             }
         }
     }
+
+    fn link_empty(self, empty: usize) {
+        unsafe {
+            ptr::write(self, Slot::Empty(empty));
+        }
+    }
+
+    fn next_empty(self) -> Option<usize> {
+        unsafe {
+            match *self {
+                Slot::Empty(empty) => Some(empty),
+                Slot::Data(_) => None,
+            }
+        }
+    }
 }
 
 #[cfg(feature = "unions")]
@@ -220,24 +1110,28 @@ impl<T> Default for Slot<T> {
 
 #[cfg(feature = "unions")]
 impl<T> SlotPtrExt<T> for *mut Slot<T> {
-    fn to_ref<'a, U>(self, _life: &'a U) -> &'a T {
+    fn data_ref<'a, U>(self, _life: &'a U) -> &'a T {
         unsafe {
             mem::transmute::<&T, &'a T>(&*(*self).data)
         }
     }
 
-    fn to_mut<'a, U>(self, _life:&'a mut U) -> &'a mut T {
+    fn data_mut<'a, U>(self, _life: &'a mut U) -> &'a mut T {
         unsafe {
             mem::transmute::<&mut T, &'a mut T>(&mut *(*self).data)
         }
     }
 
-    fn set_data(self, data: T) -> usize {
+    fn data_ptr(self) -> *mut T {
+        // `ManuallyDrop<T>` has the same layout as `T`
         unsafe {
-            let empty = ptr::read(self).empty;
-            let data = mem::ManuallyDrop::new(data);
-            ptr::write(self, Slot { data });
-            empty
+            ptr::addr_of_mut!((*self).data) as *mut T
+        }
+    }
+
+    fn occupy(self) -> usize {
+        unsafe {
+            (*self).empty
         }
     }
 
@@ -249,4 +1143,17 @@ impl<T> SlotPtrExt<T> for *mut Slot<T> {
             data
         }
     }
+
+    fn link_empty(self, empty: usize) {
+        unsafe {
+            ptr::write(self, Slot { empty });
+        }
+    }
+
+    fn next_empty(self) -> Option<usize> {
+        // Slots don't tell whether they hold data
+        unsafe {
+            Some((*self).empty)
+        }
+    }
 }
@@ -1,6 +1,8 @@
 #![cfg_attr(feature = "unions", feature(untagged_unions))]
 
 extern crate rand;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 pub mod arena;
 pub mod tree;
@@ -0,0 +1,35 @@
+use std::cmp::Ordering;
+
+/// Total order of keys, which may be decided at runtime.
+///
+/// Queries by borrowed form `Q` of the key use `Comparator<Q>`,
+/// which should agree with the order of the keys themselves.
+pub trait Comparator<K: ?Sized> {
+    fn compare(&self, a: &K, b: &K) -> Ordering;
+}
+
+/// Order given by the `Ord` impl of the key.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Natural;
+
+impl<K: Ord + ?Sized> Comparator<K> for Natural {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Reversed order of the inner comparator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Reverse<C>(pub C);
+
+impl<K: ?Sized, C: Comparator<K>> Comparator<K> for Reverse<C> {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        self.0.compare(b, a)
+    }
+}
+
+impl<K: ?Sized, F> Comparator<K> for F where F: Fn(&K, &K) -> Ordering {
+    fn compare(&self, a: &K, b: &K) -> Ordering {
+        self(a, b)
+    }
+}
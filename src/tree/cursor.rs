@@ -0,0 +1,409 @@
+use std::cmp::Ordering::Less;
+use std::ptr;
+
+use arena::{Arena, ArenaFamily, Boxed, UnsafeBoxed};
+use tree::{TreeMap, Node, Indirect, Comparator};
+use tree::rule::Rule;
+
+use super::node::Edge;
+
+/// Read-only cursor over a `TreeMap`, which moves along the `up` links of nodes.
+///
+/// The cursor points either a node of the map or a "ghost" position,
+/// which lies between the last node and the first node.
+/// Moving to the next or previous node takes amortized `O(1)`.
+pub struct Cursor<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    root: &'a Option<I>,
+    current: Option<&'a Node<K, V, R, I>>,
+}
+
+/// Cursor over a `TreeMap` which can modify the map around its position.
+pub struct CursorMut<'a, K, V, R, A, I, C> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    A: ArenaFamily + 'a,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>> + 'a,
+    C: Comparator<K> + 'a,
+{
+    map: &'a mut TreeMap<K, V, R, A, I, C>,
+    current: Option<*mut Node<K, V, R, I>>,
+}
+
+fn parent<K, V, R, I>(node: &Node<K, V, R, I>) -> Option<&Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    // Every node in the map is owned by its parent, so the link is alive
+    node.up.as_ref().map(|up| unsafe { up.get() })
+}
+
+fn is_edge_of<K, V, R, I>(edge: &Option<I>, node: &Node<K, V, R, I>) -> bool where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    edge.as_ref().is_some_and(|child| ptr::eq(&**child, node))
+}
+
+fn next<K, V, R, I>(node: &Node<K, V, R, I>) -> Option<&Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    if node.right.is_some() {
+        node.push_down_shared();
+        return node.right.first();
+    }
+
+    // Climb until the node comes from the left subtree
+    let mut node = node;
+
+    while let Some(up) = parent(node) {
+        if is_edge_of(&up.left, node) {
+            return Some(up);
+        }
+
+        node = up;
+    }
+
+    None
+}
+
+fn prev<K, V, R, I>(node: &Node<K, V, R, I>) -> Option<&Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    if node.left.is_some() {
+        node.push_down_shared();
+        return node.left.last();
+    }
+
+    // Climb until the node comes from the right subtree
+    let mut node = node;
+
+    while let Some(up) = parent(node) {
+        if is_edge_of(&up.right, node) {
+            return Some(up);
+        }
+
+        node = up;
+    }
+
+    None
+}
+
+unsafe fn first_mut<K, V, R, I>(edge: *mut Option<I>) -> Option<*mut Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    let mut node: *mut Node<K, V, R, I> = &mut **(*edge).as_mut()?;
+    (*node).push_down();
+
+    while let Some(ref mut left) = (*node).left {
+        node = &mut **left;
+        (*node).push_down();
+    }
+
+    Some(node)
+}
+
+unsafe fn last_mut<K, V, R, I>(edge: *mut Option<I>) -> Option<*mut Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    let mut node: *mut Node<K, V, R, I> = &mut **(*edge).as_mut()?;
+    (*node).push_down();
+
+    while let Some(ref mut right) = (*node).right {
+        node = &mut **right;
+        (*node).push_down();
+    }
+
+    Some(node)
+}
+
+unsafe fn parent_mut<K, V, R, I>(node: *mut Node<K, V, R, I>) -> Option<*mut Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    (*node).up.as_mut().map(|up| up.get_mut() as *mut _)
+}
+
+unsafe fn next_mut<K, V, R, I>(node: *mut Node<K, V, R, I>) -> Option<*mut Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    if (*node).right.is_some() {
+        return first_mut(&mut (*node).right);
+    }
+
+    let mut node = node;
+
+    while let Some(up) = parent_mut(node) {
+        if is_edge_of(&(*up).left, &*node) {
+            return Some(up);
+        }
+
+        node = up;
+    }
+
+    None
+}
+
+unsafe fn prev_mut<K, V, R, I>(node: *mut Node<K, V, R, I>) -> Option<*mut Node<K, V, R, I>> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    if (*node).left.is_some() {
+        return last_mut(&mut (*node).left);
+    }
+
+    let mut node = node;
+
+    while let Some(up) = parent_mut(node) {
+        if is_edge_of(&(*up).right, &*node) {
+            return Some(up);
+        }
+
+        node = up;
+    }
+
+    None
+}
+
+/// Find the edge which owns the node.
+unsafe fn edge_of<K, V, R, I>(
+    root: *mut Option<I>,
+    node: *mut Node<K, V, R, I>,
+) -> *mut Option<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    match parent_mut(node) {
+        None => root,
+        Some(up) => {
+            if is_edge_of(&(*up).left, &*node) {
+                &mut (*up).left
+            } else {
+                &mut (*up).right
+            }
+        }
+    }
+}
+
+/// Update the node and all its ancestors, after its children have been changed.
+unsafe fn retrace<K, V, R, I>(root: *mut Option<I>, node: Option<*mut Node<K, V, R, I>>) where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    let mut node = node;
+
+    while let Some(current) = node {
+        // Rotations in update keep the edge, but may move the node
+        let edge = edge_of(root, current);
+        node = parent_mut(current);
+        (*edge).update();
+    }
+}
+
+impl<'a, K, V, R, I> Cursor<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    pub(crate) fn front(root: &'a Option<I>) -> Self {
+        Cursor {
+            root,
+            current: root.first(),
+        }
+    }
+
+    pub(crate) fn back(root: &'a Option<I>) -> Self {
+        Cursor {
+            root,
+            current: root.last(),
+        }
+    }
+
+    pub(crate) fn at(root: &'a Option<I>, current: Option<&'a Node<K, V, R, I>>) -> Self {
+        Cursor {
+            root,
+            current,
+        }
+    }
+
+    pub fn key(&self) -> Option<&'a K> {
+        self.current.map(|node| node.key())
+    }
+
+    pub fn value(&self) -> Option<&'a V> {
+        self.current.map(|node| node.value())
+    }
+
+    /// Move to the next node, or to the first node if the cursor is at the ghost position.
+    pub fn move_next(&mut self) {
+        self.current = match self.current {
+            None => self.root.first(),
+            Some(node) => next(node),
+        };
+    }
+
+    /// Move to the previous node, or to the last node if the cursor is at the ghost position.
+    pub fn move_prev(&mut self) {
+        self.current = match self.current {
+            None => self.root.last(),
+            Some(node) => prev(node),
+        };
+    }
+}
+
+impl<'a, K, V, R, I> Clone for Cursor<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    fn clone(&self) -> Self {
+        Cursor {
+            root: self.root,
+            current: self.current,
+        }
+    }
+}
+
+impl<'a, K, V, R, A, I, C> CursorMut<'a, K, V, R, A, I, C> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    A: ArenaFamily + 'a,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>> + 'a,
+    C: Comparator<K> + 'a,
+{
+    pub(crate) fn front(map: &'a mut TreeMap<K, V, R, A, I, C>) -> Self {
+        let current = unsafe { first_mut(&mut map.root) };
+
+        CursorMut {
+            map,
+            current,
+        }
+    }
+
+    pub(crate) fn back(map: &'a mut TreeMap<K, V, R, A, I, C>) -> Self {
+        let current = unsafe { last_mut(&mut map.root) };
+
+        CursorMut {
+            map,
+            current,
+        }
+    }
+
+    fn node(&self) -> Option<&Node<K, V, R, I>> {
+        self.current.map(|node| unsafe { &*node })
+    }
+
+    pub fn key(&self) -> Option<&K> {
+        self.node().map(|node| node.key())
+    }
+
+    pub fn value(&self) -> Option<&V> {
+        self.node().map(|node| node.value())
+    }
+
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        self.current.map(|node| unsafe { (*node).value_mut() })
+    }
+
+    pub(crate) fn key_value_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.current.map(|node| unsafe { (*node).key_value_mut() })
+    }
+
+    /// Borrow the cursor as a read-only one at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, R, I> {
+        Cursor {
+            root: &self.map.root,
+            current: self.node(),
+        }
+    }
+
+    /// Move to the next node, or to the first node if the cursor is at the ghost position.
+    pub fn move_next(&mut self) {
+        self.current = unsafe {
+            match self.current {
+                None => first_mut(&mut self.map.root),
+                Some(node) => next_mut(node),
+            }
+        };
+    }
+
+    /// Move to the previous node, or to the last node if the cursor is at the ghost position.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe {
+            match self.current {
+                None => last_mut(&mut self.map.root),
+                Some(node) => prev_mut(node),
+            }
+        };
+    }
+
+    /// Insert a new entry right after the cursor in `O(log n)`, without moving the cursor.
+    ///
+    /// If the cursor is at the ghost position, the entry is inserted at the front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not between the current key and the next key.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        {
+            let cmp = &self.map.cmp;
+            let mut cursor = self.as_cursor();
+            assert!(cursor.key().is_none_or(|prev| cmp.compare(prev, &key) == Less),
+                "key should be greater than the current key");
+            cursor.move_next();
+            assert!(cursor.key().is_none_or(|next| cmp.compare(&key, next) == Less),
+                "key should be less than the next key");
+        }
+
+        let newbie = I::new(self.map.arena.alloc(Node::new(key, value)));
+        let root: *mut Option<I> = &mut self.map.root;
+
+        unsafe {
+            // The new node becomes either the right child of the current node,
+            // or the left child of the first node in its right subtree
+            let parent = match self.current {
+                None => first_mut(root),
+                Some(node) => first_mut(&mut (*node).right).or(Some(node)),
+            };
+            let edge = match (self.current, parent) {
+                (_, None) => root,
+                (Some(node), Some(parent)) if node == parent => &mut (*node).right,
+                (_, Some(parent)) => &mut (*parent).left,
+            };
+
+            *edge = Some(newbie);
+            retrace(root, parent);
+        }
+    }
+
+    /// Remove the current entry in `O(log n)`, and move the cursor to the next node.
+    ///
+    /// Returns `None` if the cursor is at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let current = self.current?;
+        let root: *mut Option<I> = &mut self.map.root;
+
+        unsafe {
+            self.current = next_mut(current);
+
+            let parent = parent_mut(current);
+            let node = (*edge_of(root, current)).detach();
+            retrace(root, parent);
+
+            node.map(|node| Boxed::unbox(node).into_inner())
+        }
+    }
+}
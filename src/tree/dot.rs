@@ -0,0 +1,77 @@
+use std::fmt::Debug;
+use std::io::{self, Write};
+
+use arena::{Boxed, UnsafeBoxed};
+use tree::{Node, Indirect};
+use tree::rule::Rule;
+
+/// Name of the node in the graph, which is its address.
+fn name<K, V, R, I>(node: *const Node<K, V, R, I>) -> String where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    format!("n{:p}", node)
+}
+
+/// Escape the text to be put in a quoted string of DOT.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Write the tree in the DOT language of Graphviz.
+///
+/// Nodes are named by their addresses,
+/// so `up` links pointing outside of the tree still show up.
+pub fn write_dot<K, V, R, I, W>(root: &Option<I>, w: &mut W) -> io::Result<()> where
+    K: Debug,
+    V: Debug,
+    R: Rule + Debug,
+    I: Indirect<K, V, R>,
+    W: Write,
+{
+    writeln!(w, "digraph {{")?;
+    writeln!(w, "    node [shape=box];")?;
+
+    // Visit nodes in pre-order without recursion
+    let mut stack: Vec<&I> = root.iter().collect();
+
+    while let Some(node) = stack.pop() {
+        let id = name(&**node);
+
+        let mut label = format!("key: {:?}\nvalue: {:?}\nregulator: {:?}",
+            node.key(), node.value(), node.regulator);
+        if let Some(slot) = Boxed::slot(node) {
+            label.push_str(&format!("\nslot: {}", slot));
+        }
+        writeln!(w, "    {} [label=\"{}\"];", id, escape(&label))?;
+
+        for &(child, side) in [(&node.left, "L"), (&node.right, "R")].iter() {
+            if let Some(ref child) = *child {
+                writeln!(w, "    {} -> {} [label=\"{}\"];", id, name(&**child), side)?;
+            }
+        }
+
+        if let Some(ref up) = node.up {
+            // Drawn as it is, even if the link is broken
+            let up = up.as_ptr();
+            writeln!(w, "    {} -> {} [style=dashed, constraint=false, label=\"up\"];", id, name(up))?;
+        }
+
+        node.push_down_shared();
+        stack.extend(node.right.iter());
+        stack.extend(node.left.iter());
+    }
+
+    writeln!(w, "}}")
+}
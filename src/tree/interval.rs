@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+use std::ops::{Bound, Range};
+
+use arena::ArenaFamily;
+use tree::{TreeMap, Node, Indirect, Link, Augment, TreeViolation};
+use tree::rule::Rule;
+
+/// Key of the `IntervalMap`, ordered by its start and then its end.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Interval<K>(Range<K>);
+
+impl<K: Ord> PartialOrd for Interval<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for Interval<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.0.start, &self.0.end).cmp(&(&other.0.start, &other.0.end))
+    }
+}
+
+/// Maximum end point of nonempty intervals in the subtree.
+#[derive(Debug, Clone)]
+pub struct MaxEnd<K>(Option<K>);
+
+impl<K> Default for MaxEnd<K> {
+    fn default() -> Self {
+        MaxEnd(None)
+    }
+}
+
+impl<K, V> Augment<Interval<K>, V> for MaxEnd<K> where K: Ord + Clone {
+    fn refresh<R, I>(node: &mut Node<Interval<K>, V, R, I>) where
+        R: Rule, I: Indirect<Interval<K>, V, R, Aug=Self>
+    {
+        let max = subtree_max_end(node).cloned();
+        node.aug.0 = max;
+    }
+
+    fn check<R, I>(node: &Node<Interval<K>, V, R, I>) -> bool where
+        R: Rule, I: Indirect<Interval<K>, V, R, Aug=Self>
+    {
+        max_end(node) == subtree_max_end(node)
+    }
+}
+
+/// Cached maximum end point of the subtree, or `None` if every interval in it is empty.
+fn max_end<K, V, R, I>(node: &Node<Interval<K>, V, R, I>) -> Option<&K> where
+    K: Ord + Clone,
+    R: Rule,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>>,
+{
+    // Leaf nodes are not refreshed since its creation,
+    // and refreshed ones hold `None` only if their own interval is empty too
+    node.aug.0.as_ref().or_else(|| own_end(node))
+}
+
+/// End point of the node, or `None` if its interval is empty.
+fn own_end<K, V, R, I>(node: &Node<Interval<K>, V, R, I>) -> Option<&K> where
+    K: Ord + Clone,
+    R: Rule,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>>,
+{
+    let range = &node.key().0;
+    if range.start < range.end { Some(&range.end) } else { None }
+}
+
+/// Maximum end point of the node and the cached ones of its children.
+fn subtree_max_end<K, V, R, I>(node: &Node<Interval<K>, V, R, I>) -> Option<&K> where
+    K: Ord + Clone,
+    R: Rule,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>>,
+{
+    let mut max = own_end(node);
+
+    for child in [&node.left, &node.right].iter() {
+        if let Some(ref child) = **child {
+            // `None` is less than any end point
+            max = max.max(max_end(child));
+        }
+    }
+
+    max
+}
+
+/// Map of half-open ranges, which can find ranges overlapping with given one.
+///
+/// Each node tracks the maximum end point of its subtree,
+/// so the queries skip subtrees which end before the target range.
+///
+/// Empty ranges like `5..5`, or inverted ones like `7..3`, contain no point.
+/// They can be stored and got by their key, but never overlap anything,
+/// so queries don't return them, and queries with them return nothing.
+pub struct IntervalMap<K, V, R, A, I = Link<Interval<K>, V, R, A, MaxEnd<K>>> where
+    K: Ord + Clone,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>, Inner=A::Boxed<Node<Interval<K>, V, R, I>>>,
+{
+    map: TreeMap<Interval<K>, V, R, A, I>,
+}
+
+impl<K, V, R, A, I> IntervalMap<K, V, R, A, I> where
+    K: Ord + Clone,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>, Inner=A::Boxed<Node<Interval<K>, V, R, I>>>,
+{
+    pub fn new() -> Self where A::Arena<Node<Interval<K>, V, R, I>>: Default {
+        IntervalMap {
+            map: TreeMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Check the invariants of the tree like `TreeMap::validate`,
+    /// including the maximum end points cached in nodes.
+    pub fn validate(&self) -> Result<(), TreeViolation> {
+        self.map.validate()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get(&self, range: &Range<K>) -> Option<&V> {
+        self.map.get(&Interval(range.clone()))
+    }
+
+    pub fn contains_key(&self, range: &Range<K>) -> bool {
+        self.map.contains_key(&Interval(range.clone()))
+    }
+
+    pub fn get_mut(&mut self, range: &Range<K>) -> Option<&mut V> {
+        self.map.get_mut(&Interval(range.clone()))
+    }
+
+    pub fn remove(&mut self, range: &Range<K>) -> Option<V> {
+        self.map.remove(&Interval(range.clone()))
+    }
+
+    pub fn insert(&mut self, range: Range<K>, value: V) -> Option<V> {
+        self.map.insert(Interval(range), value)
+    }
+
+    /// Iterate over ranges overlapping with `range`, ordered by their start.
+    pub fn overlapping(&self, range: Range<K>) -> Overlapping<'_, K, V, R, I> {
+        Overlapping::new(&self.map.root, range.start, Bound::Excluded(range.end))
+    }
+
+    /// Iterate over ranges which contain `point`, ordered by their start.
+    pub fn containing(&self, point: K) -> Overlapping<'_, K, V, R, I> {
+        Overlapping::new(&self.map.root, point.clone(), Bound::Included(point))
+    }
+
+    /// Check whether any range overlaps with `range`, in `O(log n)` for balanced rules.
+    pub fn any_overlap(&self, range: Range<K>) -> bool {
+        self.overlapping(range).next().is_some()
+    }
+}
+
+impl<K, V, R, A, I> Default for IntervalMap<K, V, R, A, I> where
+    K: Ord + Clone,
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<Interval<K>, V, R, I>>: Default,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>, Inner=A::Boxed<Node<Interval<K>, V, R, I>>>,
+{
+    fn default() -> Self {
+        IntervalMap::new()
+    }
+}
+
+/// Iterator over ranges which end after `low` and start before `high`.
+pub struct Overlapping<'a, K, V, R, I> where
+    K: Ord + Clone + 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>> + 'a,
+{
+    stack: Vec<&'a Node<Interval<K>, V, R, I>>,
+    low: K,
+    high: Bound<K>,
+}
+
+impl<'a, K, V, R, I> Overlapping<'a, K, V, R, I> where
+    K: Ord + Clone + 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>> + 'a,
+{
+    fn new(root: &'a Option<I>, low: K, high: Bound<K>) -> Self {
+        let mut iter = Overlapping {
+            stack: Vec::new(),
+            low,
+            high,
+        };
+
+        // Empty queries overlap nothing
+        if iter.starts_before_high(&iter.low) {
+            iter.descend(root);
+        }
+
+        iter
+    }
+
+    /// Push the left spine of the subtree, skipping subtrees ending before `low`.
+    fn descend(&mut self, mut edge: &'a Option<I>) {
+        while let Some(ref node) = *edge {
+            match max_end(node) {
+                Some(end) if *end > self.low => {}
+                _ => break,
+            }
+
+            self.stack.push(&**node);
+            edge = &node.left;
+        }
+    }
+
+    fn starts_before_high(&self, start: &K) -> bool {
+        match self.high {
+            Bound::Included(ref high) => start <= high,
+            Bound::Excluded(ref high) => start < high,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'a, K, V, R, I> Iterator for Overlapping<'a, K, V, R, I> where
+    K: Ord + Clone + 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<Interval<K>, V, R, Aug=MaxEnd<K>> + 'a,
+{
+    type Item = (&'a Range<K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            let range = &node.key().0;
+
+            // Every remaining range starts after this one
+            if !self.starts_before_high(&range.start) {
+                self.stack.clear();
+                return None;
+            }
+
+            self.descend(&node.right);
+
+            if range.end > self.low && range.start < range.end {
+                return Some((range, node.value()));
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,259 @@
+use std::marker::PhantomData;
+
+use arena::{ArenaFamily, Boxed};
+use tree::{TreeMap, Node, Indirect, Cursor, CursorMut, Comparator};
+use tree::rule::Rule;
+
+use super::node::Edge;
+
+/// In-order traversal which takes nodes out of the tree one by one.
+///
+/// Each returned node is detached from its parent and children.
+pub struct Dismantle<K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    stack: Vec<I>,
+    edge: Option<I>,
+    _marker: PhantomData<Node<K, V, R, I>>,
+}
+
+impl<K, V, R, I> Dismantle<K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    pub fn new(root: Option<I>) -> Self {
+        Dismantle {
+            stack: Vec::new(),
+            edge: root,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V, R, I> Iterator for Dismantle<K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    type Item = I;
+
+    fn next(&mut self) -> Option<I> {
+        while let Some(mut node) = self.edge.take() {
+            node.push_down();
+            self.edge = node.left.take();
+            self.stack.push(node);
+        }
+
+        let mut node = self.stack.pop()?;
+        self.edge = node.right.take();
+        node.up = None;
+        node.refresh();
+
+        Some(node)
+    }
+}
+
+impl<K, V, R, I> Drop for Dismantle<K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    fn drop(&mut self) {
+        self.edge.clear();
+
+        while let Some(node) = self.stack.pop() {
+            Some(node).clear();
+        }
+    }
+}
+
+/// Iterator over entries of a `TreeMap` in order.
+pub struct Iter<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    front: Cursor<'a, K, V, R, I>,
+    back: Cursor<'a, K, V, R, I>,
+    remaining: usize,
+}
+
+impl<'a, K, V, R, I> Iter<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    pub(crate) fn new(root: &'a Option<I>) -> Self {
+        Iter {
+            front: Cursor::front(root),
+            back: Cursor::back(root),
+            remaining: root.len(),
+        }
+    }
+}
+
+impl<'a, K, V, R, I> Clone for Iter<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    fn clone(&self) -> Self {
+        Iter {
+            front: self.front.clone(),
+            back: self.back.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, K, V, R, I> Iterator for Iter<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let entry = (self.front.key()?, self.front.value()?);
+        self.front.move_next();
+        self.remaining -= 1;
+
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, R, I> DoubleEndedIterator for Iter<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let entry = (self.back.key()?, self.back.value()?);
+        self.back.move_prev();
+        self.remaining -= 1;
+
+        Some(entry)
+    }
+}
+
+impl<'a, K, V, R, I> ExactSizeIterator for Iter<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{}
+
+/// Iterator which takes all entries out of a `TreeMap` in order.
+///
+/// Entries not yet yielded are dropped with the iterator.
+pub struct Drain<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    nodes: Dismantle<K, V, R, I>,
+    _marker: PhantomData<&'a mut Option<I>>,
+}
+
+impl<'a, K, V, R, I> Drain<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    pub(crate) fn new(root: &'a mut Option<I>) -> Self {
+        Drain {
+            nodes: Dismantle::new(root.take()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, R, I> Iterator for Drain<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.nodes.next().map(|node| Boxed::unbox(node).into_inner())
+    }
+}
+
+/// Iterator which removes entries matching the predicate from a `TreeMap`, in order.
+///
+/// Entries are removed in place by a cursor, each taking `O(log n)`,
+/// so the entries not visited yet stay in the map even if the iterator is leaked.
+pub struct ExtractIf<'a, K, V, R, A, I, C, F> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    A: ArenaFamily + 'a,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>> + 'a,
+    C: Comparator<K> + 'a,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    cursor: CursorMut<'a, K, V, R, A, I, C>,
+    pred: F,
+}
+
+impl<'a, K, V, R, A, I, C, F> ExtractIf<'a, K, V, R, A, I, C, F> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    A: ArenaFamily + 'a,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>> + 'a,
+    C: Comparator<K> + 'a,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    pub(crate) fn new(map: &'a mut TreeMap<K, V, R, A, I, C>, pred: F) -> Self {
+        ExtractIf {
+            cursor: CursorMut::front(map),
+            pred,
+        }
+    }
+}
+
+impl<'a, K, V, R, A, I, C, F> Iterator for ExtractIf<'a, K, V, R, A, I, C, F> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    A: ArenaFamily + 'a,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>> + 'a,
+    C: Comparator<K> + 'a,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        // The cursor stops at the ghost position after the last entry
+        while let Some((key, value)) = self.cursor.key_value_mut() {
+            if (self.pred)(key, value) {
+                return self.cursor.remove_current();
+            }
+
+            self.cursor.move_next();
+        }
+
+        None
+    }
+}
@@ -0,0 +1,73 @@
+use std::ops::{Deref, DerefMut};
+
+use arena::{ArenaFamily, Boxed};
+use tree::{Node, Indirect, Augment};
+use tree::rule::Rule;
+
+/// Box of a tree node allocated from the arena family `A`, with augmentation `G`.
+///
+/// It's the default `Indirect` of the trees, so they can be named like
+/// `TreeMap<K, V, R, VecArena>` without declaring the box type.
+pub struct Link<K, V, R, A, G = ()>(A::Boxed<Node<K, V, R, Self>>) where
+    R: Rule,
+    A: ArenaFamily,
+    G: Augment<K, V>;
+
+impl<K, V, R, A, G> Deref for Link<K, V, R, A, G> where
+    R: Rule,
+    A: ArenaFamily,
+    G: Augment<K, V>,
+{
+    type Target = Node<K, V, R, Self>;
+
+    fn deref(&self) -> &Node<K, V, R, Self> {
+        &self.0
+    }
+}
+
+impl<K, V, R, A, G> DerefMut for Link<K, V, R, A, G> where
+    R: Rule,
+    A: ArenaFamily,
+    G: Augment<K, V>,
+{
+    fn deref_mut(&mut self) -> &mut Node<K, V, R, Self> {
+        &mut self.0
+    }
+}
+
+impl<K, V, R, A, G> Boxed<Node<K, V, R, Self>> for Link<K, V, R, A, G> where
+    R: Rule,
+    A: ArenaFamily,
+    G: Augment<K, V>,
+{
+    type Unsafe = <A::Boxed<Node<K, V, R, Self>> as Boxed<Node<K, V, R, Self>>>::Unsafe;
+
+    fn unbox(boxed: Self) -> Node<K, V, R, Self> {
+        Boxed::unbox(boxed.0)
+    }
+
+    fn to_unsafe(boxed: &mut Self) -> Self::Unsafe {
+        Boxed::to_unsafe(&mut boxed.0)
+    }
+
+    fn is_shared(boxed: &Self) -> bool {
+        Boxed::is_shared(&boxed.0)
+    }
+
+    fn slot(boxed: &Self) -> Option<usize> {
+        Boxed::slot(&boxed.0)
+    }
+}
+
+impl<K, V, R, A, G> Indirect<K, V, R> for Link<K, V, R, A, G> where
+    R: Rule,
+    A: ArenaFamily,
+    G: Augment<K, V>,
+{
+    type Inner = A::Boxed<Node<K, V, R, Self>>;
+    type Aug = G;
+
+    fn new(inner: Self::Inner) -> Self {
+        Link(inner)
+    }
+}
@@ -1,88 +1,248 @@
 use std::borrow::Borrow;
+use std::cmp::Ordering::{self, Less, Equal, Greater};
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::iter::FromIterator;
 use std::marker::PhantomData;
+use std::ops::{Bound, Index, RangeBounds};
 
-use arena::Arena;
-use tree::{Node, Indirect};
+use arena::{Arena, ArenaFamily, Boxed};
+use tree::{Node, Indirect, Link, Lazy, Cursor, CursorMut, Comparator, Natural, TreeViolation, Stats};
+use tree::{Pod, SnapshotError};
 use tree::rule::Rule;
 
-use super::node::Edge;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+#[cfg(feature = "serde")]
+use serde::de::{Visitor, MapAccess};
+
+use super::node::{Edge, build_balanced, clone_tree};
+use super::merge;
+use super::validate::validate;
+use super::dot::write_dot;
+use super::stats::stats;
+use super::snapshot::{write_snapshot, read_snapshot};
+use super::iter::{Dismantle, Iter, Drain, ExtractIf};
 
 #[macro_export]
+#[deprecated(note = "use `TreeMap<K, V, R, A>` instead")]
 macro_rules! treemap {
     ($name:ident, $K:ty, $V:ty, $R:ty, $A:ident, $B:ident, $I:ident) => (
-        treemap!{!_impl
-            $name, $K, $V, $R, $A, $B, $I,
-            $crate::tree::Node<$K, $V, $R, $I>
-        }
+        type $I = $crate::tree::Link<$K, $V, $R, $A>;
+        type $name = $crate::tree::TreeMap<$K, $V, $R, $A, $I>;
+    );
+    ($name:ident, $K:ty, $V:ty, $R:ty, $A:ident, $B:ident, $I:ident, $C:ty) => (
+        type $I = $crate::tree::Link<$K, $V, $R, $A>;
+        type $name = $crate::tree::TreeMap<$K, $V, $R, $A, $I, $C>;
+    );
+    ($name:ident, $K:ty, $V:ty, $R:ty, $A:ident, $B:ident, $I:ident, $C:ty, $G:ty) => (
+        type $I = $crate::tree::Link<$K, $V, $R, $A, $G>;
+        type $name = $crate::tree::TreeMap<$K, $V, $R, $A, $I, $C>;
     );
-    (!_impl $name:ident, $K:ty, $V:ty, $R:ty, $A:ident, $B:ident, $I:ident, $Node:ty) => (
-        type $name = TreeMap<$K, $V, $R, $A<$Node>, $I>;
+}
 
-        struct $I($B<$Node>);
+/// Ordered map backed by a tree balanced by the rule `R`.
+///
+/// Nodes are allocated from the arena family `A`, like `TreeMap<K, V, RevTreap, VecArena>`.
+/// `I` picks the box of nodes, which may carry augmented data.
+pub struct TreeMap<K, V, R, A, I = Link<K, V, R, A>, C = Natural> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    pub(crate) arena: A::Arena<Node<K, V, R, I>>,
+    pub(crate) root: Option<I>,
+    pub(crate) cmp: C,
+    _marker: PhantomData<Node<K, V, R, I>>,
+}
 
-        impl ::std::ops::Deref for $I {
-            type Target = $Node;
+impl<K, V, R, A, I, C> TreeMap<K, V, R, A, I, C> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    pub fn new() -> Self where C: Default, A::Arena<Node<K, V, R, I>>: Default {
+        TreeMap::with_comparator(C::default())
+    }
 
-            fn deref(&self) -> &$Node {
-                &*self.0
-            }
-        }
+    /// Create an empty map whose keys are ordered by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self where A::Arena<Node<K, V, R, I>>: Default {
+        TreeMap::with_comparator_in(cmp, Default::default())
+    }
 
-        impl ::std::ops::DerefMut for $I {
-            fn deref_mut(&mut self) -> &mut $Node {
-                &mut *self.0
-            }
+    /// Create an empty map allocating nodes in `arena`,
+    /// which is needed for arenas without `Default`, like `ArenaRef`.
+    pub fn new_in(arena: A::Arena<Node<K, V, R, I>>) -> Self where C: Default {
+        TreeMap::with_comparator_in(C::default(), arena)
+    }
+
+    /// Create an empty map whose keys are ordered by `cmp`, allocating nodes in `arena`.
+    pub fn with_comparator_in(cmp: C, arena: A::Arena<Node<K, V, R, I>>) -> Self {
+        TreeMap {
+            arena,
+            root: None,
+            cmp,
+            _marker: Default::default(),
         }
+    }
 
-        impl $crate::arena::Boxed<$Node> for $I {
-            type Unsafe = <$B<$Node> as $crate::arena::Boxed<$Node>>::Unsafe;
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
 
-            fn unbox(boxed: Self) -> $Node {
-                $B::<$Node>::unbox(boxed.0)
-            }
+    /// Arena which the nodes are allocated in.
+    pub fn arena(&self) -> &A::Arena<Node<K, V, R, I>> {
+        &self.arena
+    }
+
+    /// Build a balanced map from entries sorted by their keys, in `O(n)`.
+    ///
+    /// If several entries have the same key, the last one is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not sorted.
+    pub fn from_sorted_iter<T>(iter: T) -> Self where
+        C: Default, T: IntoIterator<Item=(K, V)>, A::Arena<Node<K, V, R, I>>: Default
+    {
+        TreeMap::from_sorted_iter_by(C::default(), iter)
+    }
 
-            fn to_unsafe(boxed: &mut Self) -> Self::Unsafe {
-                $B::<$Node>::to_unsafe(&mut boxed.0)
+    /// Build a balanced map from entries sorted by `cmp`, in `O(n)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not sorted.
+    pub fn from_sorted_iter_by<T>(cmp: C, iter: T) -> Self where
+        T: IntoIterator<Item=(K, V)>, A::Arena<Node<K, V, R, I>>: Default
+    {
+        let iter = iter.into_iter();
+        let mut map = Self::with_comparator(cmp);
+        let mut nodes: Vec<I> = Vec::with_capacity(iter.size_hint().0);
+        map.arena.reserve(iter.size_hint().0);
+
+        for (key, value) in iter {
+            if let Some(last) = nodes.last_mut() {
+                match map.cmp.compare(last.key(), &key) {
+                    Less => {}
+                    Equal => {
+                        **last = Node::new(key, value);
+                        continue;
+                    }
+                    Greater => panic!("keys should be sorted"),
+                }
             }
+
+            nodes.push(I::new(map.arena.alloc(Node::new(key, value))));
         }
 
-        impl $crate::tree::Indirect<$K, $V, $R> for $I {
-            type Inner = $B<$Node>;
+        let len = nodes.len();
+        map.root = build_balanced(&mut nodes.into_iter(), len);
+
+        map
+    }
+
+    pub fn clear(&mut self) {
+        self.root.clear();
+    }
+
+    /// Keep only entries for which `f` returns `true`, rebuilding the tree in `O(n)`.
+    ///
+    /// The tree is balanced afterwards, even for `Noop`. To keep its shape when few entries go,
+    /// use `extract_if`, which removes them in place in `O(log n)` each.
+    /// If `f` panics, the entry it's called on is removed, and the others are kept.
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &mut V) -> bool {
+        let nodes: Vec<I> = Dismantle::new(self.root.take()).collect();
+        let mut rebuild = Rebuild {
+            kept: Vec::with_capacity(nodes.len()),
+            rest: nodes.into_iter(),
+            root: &mut self.root,
+            _marker: PhantomData,
+        };
 
-            fn new(b: $B<$Node>) -> Self {
-                $I(b)
+        for mut node in rebuild.rest.by_ref() {
+            let keep = {
+                let (key, value) = node.key_value_mut();
+                f(key, value)
+            };
+
+            if keep {
+                rebuild.kept.push(node);
             }
         }
-    );
-}
+    }
 
-pub struct TreeMap<K, V, R, A, I> where
-    K: Ord,
-    R: Rule,
-    A: Arena<Node<K, V, R, I>, I::Inner>,
-    I: Indirect<K, V, R>,
-{
-    arena: A,
-    root: Option<I>,
-    _marker: PhantomData<Node<K, V, R, I>>,
-}
+    /// Iterate over entries in order.
+    pub fn iter(&self) -> Iter<'_, K, V, R, I> {
+        Iter::new(&self.root)
+    }
 
-impl<K, V, R, A, I> TreeMap<K, V, R, A, I> where
-    K: Ord,
-    R: Rule,
-    A: Arena<Node<K, V, R, I>, I::Inner>,
-    I: Indirect<K, V, R>,
-{
-    pub fn new() -> Self {
-        TreeMap {
-            arena: A::default(),
-            root: None,
-            _marker: Default::default(),
-        }
+    /// Take all entries out of the map, in order.
+    pub fn drain(&mut self) -> Drain<'_, K, V, R, I> {
+        Drain::new(&mut self.root)
     }
 
-    pub fn clear(&mut self) {
-        self.root = None;
+    /// Lazily remove entries for which `pred` returns `true`, in order.
+    ///
+    /// Entries not visited yet are kept when the iterator is dropped.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, R, A, I, C, F> where
+        F: FnMut(&K, &mut V) -> bool
+    {
+        ExtractIf::new(self, pred)
+    }
+
+    /// Check the invariants of the tree, returning the first violation found.
+    ///
+    /// Keys should be in order, and each node should have the right parent link,
+    /// cached size and augmented data, and keep the rule by `Rule::check`.
+    /// It takes `O(n)`.
+    pub fn validate(&self) -> Result<(), TreeViolation> {
+        validate(&self.root, &self.cmp, true)
+    }
+
+    /// Measure the shape of the tree and the occupancy of the arena, in `O(n)`.
+    pub fn stats(&self) -> Stats {
+        stats(&self.root, self.arena.stats())
+    }
+
+    /// Render the tree in the DOT language of Graphviz, for debugging.
+    ///
+    /// Each node shows its key, value and regulator, and its slot if the arena numbers them.
+    /// Child links are solid, and `up` links are dashed.
+    pub fn to_dot(&self) -> String where K: Debug, V: Debug, R: Debug {
+        let mut dot = Vec::new();
+        self.write_dot(&mut dot).expect("writing to Vec should not fail");
+        String::from_utf8(dot).expect("Debug output should be UTF-8")
+    }
+
+    /// Write the tree in the DOT language of Graphviz, like `to_dot`.
+    pub fn write_dot<W>(&self, w: &mut W) -> io::Result<()> where
+        K: Debug, V: Debug, R: Debug, W: io::Write
+    {
+        write_dot(&self.root, w)
+    }
+
+    /// Write the map in the compact binary layout documented in `tree::snapshot`,
+    /// which keeps the shape of the tree.
+    pub fn write_snapshot<W: io::Write>(&self, w: &mut W) -> io::Result<()> where K: Pod, V: Pod {
+        write_snapshot(&self.root, w)
+    }
+
+    /// Load a map written by `write_snapshot` in a single pass, without rebalancing.
+    ///
+    /// The checksum catches truncated or corrupted input, but the order of keys is not checked.
+    /// Call `validate` if the snapshot may be written with another comparator.
+    pub fn read_snapshot<S: io::Read>(r: S) -> Result<Self, SnapshotError> where
+        K: Pod, V: Pod, C: Default, A::Arena<Node<K, V, R, I>>: Default
+    {
+        let mut map = Self::new();
+        let arena = &map.arena;
+        map.root = read_snapshot(r, |node| I::new(arena.alloc(node)))?;
+
+        Ok(map)
     }
 
     pub fn len(&self) -> usize {
@@ -90,46 +250,476 @@ impl<K, V, R, A, I> TreeMap<K, V, R, A, I> where
     }
 
     pub fn is_empty(&self) -> bool {
-        self.root.is_some()
+        self.root.is_none()
     }
 
-    pub fn get<'a, Q>(&self, key: &Q) -> Option<&V> where
-        K: Borrow<Q>, Q: Ord + ?Sized
+    pub fn get<Q>(&self, key: &Q) -> Option<&V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
     {
-        self.root.get(key)
+        self.root.get(key, &self.cmp)
     }
 
-    pub fn contains_key<'a, Q>(&self, key: &Q) -> bool where
-        K: Borrow<Q>, Q: Ord + ?Sized
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
     {
-        self.root.get(key).is_some()
+        self.root.get(key, &self.cmp).is_some()
     }
 
-    pub fn get_mut<'a, Q>(&mut self, key: &Q) -> Option<&mut V> where
-        K: Borrow<Q>, Q: Ord + ?Sized
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
     {
-        self.root.get_mut(key)
+        self.root.get_mut(key, &self.cmp)
     }
 
-    pub fn remove<'a, Q>(&mut self, key: &Q) -> Option<V> where
-        K: Borrow<Q>, Q: Ord + ?Sized
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
     {
-        self.root.remove(key)
+        self.root.remove(key, &self.cmp)
     }
 
+    /// Insert the entry, returning the old value if an equal key is in the map.
+    ///
+    /// The old key is replaced too, which matters if equal keys can be told apart.
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
         let node = self.arena.alloc(Node::new(key, value));
-        self.root.insert(I::new(node))
+        Edge::insert(&mut self.root, I::new(node), &self.cmp)
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.root.first().map(|node| (node.key(), node.value()))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.root.last().map(|node| (node.key(), node.value()))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.root.pop_first().map(|node| Boxed::unbox(node).into_inner())
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.root.pop_last().map(|node| Boxed::unbox(node).into_inner())
+    }
+
+    /// Take nodes of the other map, moving them into this arena if they don't share it.
+    fn adopt(&self, mut other: Self) -> Option<I> {
+        let root = other.root.take();
+
+        if self.arena.shares_with(&other.arena) {
+            return root;
+        }
+
+        let len = root.len();
+        self.arena.reserve(len);
+
+        let mut nodes = Dismantle::new(root).map(|node| {
+            I::new(self.arena.alloc(Boxed::unbox(node)))
+        });
+
+        build_balanced(&mut nodes, len)
+    }
+
+    /// Move all entries of `other` into the map.
+    ///
+    /// For keys in both maps, `f` merges the value of `other` into the value of `self`.
+    pub fn union_with<F>(&mut self, other: Self, mut f: F) where F: FnMut(&K, &mut V, V) {
+        let theirs = self.adopt(other);
+        let ours = self.root.take();
+        self.root = merge::union(ours, theirs, &self.cmp, false, &mut f);
+        self.detach_root();
+    }
+
+    /// Keep only entries whose keys are also in `other`.
+    ///
+    /// `f` merges the value of `other` into the value of `self`.
+    pub fn intersection_with<F>(&mut self, other: Self, mut f: F) where F: FnMut(&K, &mut V, V) {
+        let theirs = self.adopt(other);
+        let ours = self.root.take();
+        self.root = merge::intersection(ours, theirs, &self.cmp, false, &mut f);
+        self.detach_root();
+    }
+
+    /// Remove all entries whose keys are in `other`.
+    pub fn difference(&mut self, mut other: Self) {
+        let theirs = other.root.take();
+        let ours = self.root.take();
+        self.root = merge::difference(ours, theirs, &self.cmp);
+        self.detach_root();
+    }
+
+    /// Apply the tag to all entries whose keys are in `range`, in `O(log n)`.
+    ///
+    /// The tag is left pending at the top of the range, and pushed down to
+    /// the entries below it as later operations, including reads, reach them.
+    pub fn update_range<Q, T>(&mut self, range: T, tag: <I::Aug as Lazy<K, V>>::Tag) where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>, T: RangeBounds<Q>, I::Aug: Lazy<K, V>
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.root.rank(key, &self.cmp, false),
+            Bound::Excluded(key) => self.root.rank(key, &self.cmp, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.root.rank(key, &self.cmp, true),
+            Bound::Excluded(key) => self.root.rank(key, &self.cmp, false),
+            Bound::Unbounded => self.len(),
+        };
+
+        if start >= end {
+            return;
+        }
+
+        let mut mid = self.root.split_at(start);
+        let rest = mid.split_at(end - start);
+
+        if let Some(ref mut node) = mid {
+            I::Aug::apply(node, &tag);
+        }
+
+        self.root.join(mid);
+        self.root.join(rest);
+    }
+
+    fn detach_root(&mut self) {
+        if let Some(ref mut root) = self.root {
+            root.up = None;
+        }
+    }
+
+    /// Entry with the greatest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        self.root.below(key, &self.cmp, true).map(|node| (node.key(), node.value()))
+    }
+
+    /// Entry with the least key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        self.root.above(key, &self.cmp, true).map(|node| (node.key(), node.value()))
+    }
+
+    /// Cursor pointing the first entry whose key is greater than or equal to `key`,
+    /// or the ghost position if there's no such entry.
+    pub fn lower_bound<Q>(&self, key: &Q) -> Cursor<'_, K, V, R, I> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        Cursor::at(&self.root, self.root.above(key, &self.cmp, true))
+    }
+
+    /// Cursor pointing the first entry whose key is greater than `key`,
+    /// or the ghost position if there's no such entry.
+    pub fn upper_bound<Q>(&self, key: &Q) -> Cursor<'_, K, V, R, I> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        Cursor::at(&self.root, self.root.above(key, &self.cmp, false))
+    }
+
+    /// Cursor pointing the first entry, or the ghost position if the map is empty.
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, R, I> {
+        Cursor::front(&self.root)
+    }
+
+    /// Cursor pointing the last entry, or the ghost position if the map is empty.
+    pub fn cursor_back(&self) -> Cursor<'_, K, V, R, I> {
+        Cursor::back(&self.root)
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, V, R, A, I, C> {
+        CursorMut::front(self)
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, K, V, R, A, I, C> {
+        CursorMut::back(self)
     }
 }
 
-impl<K, V, R, A, I> Default for TreeMap<K, V, R, A, I> where
-    K: Ord,
+impl<K, V, R, A, I, C> Default for TreeMap<K, V, R, A, I, C> where
     R: Rule,
-    A: Arena<Node<K, V, R, I>, I::Inner>,
-    I: Indirect<K, V, R>,
+    A: ArenaFamily,
+    A::Arena<Node<K, V, R, I>>: Default,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K> + Default,
 {
     fn default() -> Self {
-        TreeMap::new()
+        TreeMap::with_comparator(C::default())
+    }
+}
+
+impl<K, V, R, A, I, C> FromIterator<(K, V)> for TreeMap<K, V, R, A, I, C> where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<K, V, R, I>>: Default,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K> + Default,
+{
+    fn from_iter<T>(iter: T) -> Self where T: IntoIterator<Item=(K, V)> {
+        let cmp = C::default();
+        let mut entries: Vec<_> = iter.into_iter().collect();
+        // Stable sort keeps the last one of the same keys at last
+        entries.sort_by(|a, b| cmp.compare(&a.0, &b.0));
+
+        TreeMap::from_sorted_iter_by(cmp, entries)
+    }
+}
+
+impl<K, V, R, A, I, C> Extend<(K, V)> for TreeMap<K, V, R, A, I, C> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn extend<T>(&mut self, iter: T) where T: IntoIterator<Item=(K, V)> {
+        let iter = iter.into_iter();
+        self.arena.reserve(iter.size_hint().0);
+
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K, V, R, A, I, C> IntoIterator for &'a TreeMap<K, V, R, A, I, C> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    A: ArenaFamily + 'a,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>> + 'a,
+    C: Comparator<K> + 'a,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, R, I>;
+
+    fn into_iter(self) -> Iter<'a, K, V, R, I> {
+        self.iter()
+    }
+}
+
+impl<K, V, R, A, I, C> Debug for TreeMap<K, V, R, A, I, C> where
+    K: Debug,
+    V: Debug,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, R, A, I, C> Clone for TreeMap<K, V, R, A, I, C> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    A: ArenaFamily,
+    A::Arena<Node<K, V, R, I>>: Clone,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    I::Aug: Clone,
+    C: Comparator<K> + Clone,
+{
+    /// Copy all nodes keeping the shape of the tree, into the clone of the arena.
+    ///
+    /// Cloned arena may be shared with the original one, like `VecArena`.
+    fn clone(&self) -> Self {
+        let arena = self.arena.clone();
+        arena.reserve(self.len());
+        let root = clone_tree(&self.root, |node| I::new(arena.alloc(node)));
+
+        TreeMap {
+            arena,
+            root,
+            cmp: self.cmp.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Maps are equal if they have equal entries in the same order, regardless of their shapes.
+impl<K, V, R, A, I, C> PartialEq for TreeMap<K, V, R, A, I, C> where
+    K: PartialEq,
+    V: PartialEq,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K, V, R, A, I, C> Eq for TreeMap<K, V, R, A, I, C> where
+    K: Eq,
+    V: Eq,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{}
+
+impl<K, V, R, A, I, C> Hash for TreeMap<K, V, R, A, I, C> where
+    K: Hash,
+    V: Hash,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+/// Maps are compared lexicographically by their entries.
+impl<K, V, R, A, I, C> PartialOrd for TreeMap<K, V, R, A, I, C> where
+    K: PartialOrd,
+    V: PartialOrd,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K, V, R, A, I, C> Ord for TreeMap<K, V, R, A, I, C> where
+    K: Ord,
+    V: Ord,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K, V, R, A, I, C, Q> Index<&Q> for TreeMap<K, V, R, A, I, C> where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K> + Comparator<Q>,
+{
+    type Output = V;
+
+    /// # Panics
+    ///
+    /// Panics if the key is not in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+/// Serialize as a map ordered by the keys.
+#[cfg(feature = "serde")]
+impl<K, V, R, A, I, C> Serialize for TreeMap<K, V, R, A, I, C> where
+    K: Serialize,
+    V: Serialize,
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self)
+    }
+}
+
+/// Deserialize from a map in any order, building a balanced tree in bulk.
+///
+/// If several entries have the same key, the last one is kept.
+#[cfg(feature = "serde")]
+impl<'de, K, V, R, A, I, C> Deserialize<'de> for TreeMap<K, V, R, A, I, C> where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<K, V, R, I>>: Default,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K> + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(TreeMapVisitor(PhantomData))
+    }
+}
+
+#[cfg(feature = "serde")]
+struct TreeMapVisitor<K, V, R, A, I, C>(PhantomData<(K, V, R, A, I, C)>);
+
+#[cfg(feature = "serde")]
+impl<'de, K, V, R, A, I, C> Visitor<'de> for TreeMapVisitor<K, V, R, A, I, C> where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<K, V, R, I>>: Default,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K> + Default,
+{
+    type Value = TreeMap<K, V, R, A, I, C>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut access: M) -> Result<Self::Value, M::Error> {
+        // Don't trust the size hint too much, it may come from a malicious input
+        let mut entries = Vec::with_capacity(access.size_hint().unwrap_or(0).min(4096));
+
+        while let Some(entry) = access.next_entry()? {
+            entries.push(entry);
+        }
+
+        // Stable sort keeps the same keys in their order, so the last one wins
+        let cmp = C::default();
+        entries.sort_by(|a: &(K, V), b: &(K, V)| cmp.compare(&a.0, &b.0));
+
+        Ok(TreeMap::from_sorted_iter_by(cmp, entries))
+    }
+}
+
+/// Nodes of `TreeMap::retain`, which are built back into the tree when dropped,
+/// even if the predicate panics.
+struct Rebuild<'a, K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R> + 'a,
+{
+    kept: Vec<I>,
+    rest: ::std::vec::IntoIter<I>,
+    root: &'a mut Option<I>,
+    _marker: PhantomData<Node<K, V, R, I>>,
+}
+
+impl<'a, K, V, R, I> Drop for Rebuild<'a, K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R> + 'a,
+{
+    fn drop(&mut self) {
+        let len = self.kept.len() + self.rest.len();
+        let mut nodes = self.kept.drain(..).chain(&mut self.rest);
+        *self.root = build_balanced(&mut nodes, len);
+    }
+}
+
+impl<K, V, R, A, I, C> Drop for TreeMap<K, V, R, A, I, C> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    fn drop(&mut self) {
+        // Dropping nodes recursively may overflow the stack on deep trees
+        self.root.clear();
     }
 }
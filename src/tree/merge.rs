@@ -0,0 +1,227 @@
+use std::mem::swap;
+
+use arena::Boxed;
+use tree::{Node, Indirect, Comparator};
+use tree::rule::Rule;
+
+use super::node::Edge;
+
+/// Take the value of a node which is detached from its children.
+fn into_value<K, V, R, I>(node: I) -> V where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    Boxed::unbox(node).into_inner().1
+}
+
+/// Merge the value of `other` into the value of `node`.
+///
+/// If `swapped`, `node` came from the other tree,
+/// so the values are swapped first to keep the order of arguments for `f`.
+fn merge_value<K, V, R, I, F>(node: &mut Node<K, V, R, I>, other: I, swapped: bool, f: &mut F) where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    F: FnMut(&K, &mut V, V),
+{
+    let mut value = into_value(other);
+    let (key, current) = node.key_value_mut();
+
+    if swapped {
+        swap(current, &mut value);
+    }
+
+    f(key, current, value);
+}
+
+/// Pending work of a merge, kept on an explicit stack instead of recursion,
+/// as trees of `Noop` may be as deep as they are long.
+enum Task<I> {
+    /// Merge the two subtrees, with whether they are swapped.
+    Merge(Option<I>, Option<I>, bool),
+    /// Join the last two merged subtrees with the node, merging the value of `mid` into it first.
+    /// Without the node, the subtrees are joined directly.
+    Join(Option<I>, Option<I>, bool),
+}
+
+/// Result of a single step of a merge.
+enum Step<I> {
+    Done(Option<I>),
+    /// The left and right pairs are merged, then joined by the node and `mid` like `Task::Join`.
+    Split {
+        node: Option<I>,
+        mid: Option<I>,
+        left: (Option<I>, Option<I>),
+        right: (Option<I>, Option<I>),
+        swapped: bool,
+    },
+}
+
+/// Merge two trees by `step`, running the steps of their subtrees in order of keys.
+fn merge<K, V, R, I, F, S>(
+    ours: Option<I>,
+    theirs: Option<I>,
+    swapped: bool,
+    f: &mut F,
+    mut step: S,
+) -> Option<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    F: FnMut(&K, &mut V, V),
+    S: FnMut(Option<I>, Option<I>, bool) -> Step<I>,
+{
+    let mut tasks = vec![Task::Merge(ours, theirs, swapped)];
+    let mut merged: Vec<Option<I>> = Vec::new();
+
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Merge(ours, theirs, swapped) => match step(ours, theirs, swapped) {
+                Step::Done(tree) => merged.push(tree),
+                Step::Split { node, mid, left, right, swapped } => {
+                    // The left pair is merged first, then the right pair
+                    tasks.push(Task::Join(node, mid, swapped));
+                    tasks.push(Task::Merge(right.0, right.1, swapped));
+                    tasks.push(Task::Merge(left.0, left.1, swapped));
+                }
+            },
+            Task::Join(node, mid, swapped) => {
+                let right = merged.pop().expect("right subtree should be merged");
+                let mut left = merged.pop().expect("left subtree should be merged");
+
+                match node {
+                    Some(mut node) => {
+                        if let Some(mid) = mid {
+                            merge_value(&mut node, mid, swapped, f);
+                        }
+                        left.join_with(node, right);
+                    }
+                    None => left.join(right),
+                }
+
+                merged.push(left);
+            }
+        }
+    }
+
+    merged.pop().expect("trees should be merged")
+}
+
+/// Split the root with higher rank out of the two trees, and the other tree with its key.
+fn split_root<K, V, R, I, C>(mut ours: I, mut theirs: I, cmp: &C, mut swapped: bool) -> Step<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    C: Comparator<K>,
+{
+    if theirs.regulator.outranks(&ours.regulator) {
+        swap(&mut ours, &mut theirs);
+        swapped = !swapped;
+    }
+
+    ours.push_down();
+    let mut theirs = Some(theirs);
+    let (mid, theirs_right) = theirs.split_key(ours.key(), cmp);
+    let left = (ours.left.take(), theirs);
+    let right = (ours.right.take(), theirs_right);
+
+    Step::Split { node: Some(ours), mid, left, right, swapped }
+}
+
+/// Union of two trees, by splitting one of them with the root key of the other.
+///
+/// Root with higher rank is kept on top, so it takes `O(m log(n/m + 1))`
+/// for treap-like rules.
+pub fn union<K, V, R, I, C, F>(
+    ours: Option<I>,
+    theirs: Option<I>,
+    cmp: &C,
+    swapped: bool,
+    f: &mut F,
+) -> Option<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    C: Comparator<K>,
+    F: FnMut(&K, &mut V, V),
+{
+    merge(ours, theirs, swapped, f, |ours, theirs, swapped| {
+        let (ours, theirs) = match (ours, theirs) {
+            (None, tree) | (tree, None) => return Step::Done(tree),
+            (Some(ours), Some(theirs)) => (ours, theirs),
+        };
+
+        split_root(ours, theirs, cmp, swapped)
+    })
+}
+
+/// Intersection of two trees, in the same manner as `union`.
+pub fn intersection<K, V, R, I, C, F>(
+    ours: Option<I>,
+    theirs: Option<I>,
+    cmp: &C,
+    swapped: bool,
+    f: &mut F,
+) -> Option<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    C: Comparator<K>,
+    F: FnMut(&K, &mut V, V),
+{
+    merge(ours, theirs, swapped, f, |ours, theirs, swapped| {
+        let (ours, theirs) = match (ours, theirs) {
+            (Some(ours), Some(theirs)) => (ours, theirs),
+            (mut ours, mut theirs) => {
+                ours.clear();
+                theirs.clear();
+                return Step::Done(None);
+            }
+        };
+
+        match split_root(ours, theirs, cmp, swapped) {
+            // The node is dropped without a match in the other tree
+            Step::Split { mid: None, left, right, swapped, .. } =>
+                Step::Split { node: None, mid: None, left, right, swapped },
+            step => step,
+        }
+    })
+}
+
+/// Nodes of `ours` whose keys are not in `theirs`, in the same manner as `union`.
+pub fn difference<K, V, R, I, C>(ours: Option<I>, theirs: Option<I>, cmp: &C) -> Option<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    C: Comparator<K>,
+{
+    let mut f = |_: &K, _: &mut V, _: V| {};
+
+    merge(ours, theirs, false, &mut f, |ours, theirs, _| {
+        let (mut ours, mut theirs) = match (ours, theirs) {
+            (tree, None) => return Step::Done(tree),
+            (None, mut theirs) => {
+                theirs.clear();
+                return Step::Done(None);
+            }
+            (Some(ours), Some(theirs)) => (ours, theirs),
+        };
+
+        if theirs.regulator.outranks(&ours.regulator) {
+            // Split ours with their root, which is removed anyway
+            theirs.push_down();
+            let mut ours = Some(ours);
+            let (_, ours_right) = ours.split_key(theirs.key(), cmp);
+            let left = (ours, theirs.left.take());
+            let right = (ours_right, theirs.right.take());
+
+            return Step::Split { node: None, mid: None, left, right, swapped: false };
+        }
+
+        ours.push_down();
+        let mut theirs = Some(theirs);
+        let (mid, theirs_right) = theirs.split_key(ours.key(), cmp);
+        let left = (ours.left.take(), theirs);
+        let right = (ours.right.take(), theirs_right);
+        let node = match mid {
+            Some(_) => None,
+            None => Some(ours),
+        };
+
+        Step::Split { node, mid: None, left, right, swapped: false }
+    })
+}
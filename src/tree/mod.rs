@@ -1,7 +1,32 @@
-
 mod map;
 mod node;
+mod link;
+mod seq;
+mod interval;
+mod persistent;
+mod cursor;
+mod merge;
+mod iter;
+mod compare;
+mod multi;
+mod shift;
+mod validate;
+mod dot;
+mod stats;
+pub mod snapshot;
 pub mod rule;
 
 pub use self::map::TreeMap;
-pub use self::node::{Node, Indirect};
+pub use self::node::{Node, Indirect, Augment, Lazy};
+pub use self::link::Link;
+pub use self::seq::{TreeSeq, Flip};
+pub use self::interval::{IntervalMap, Interval, MaxEnd, Overlapping};
+pub use self::persistent::{PersistentMap, RcLink};
+pub use self::cursor::{Cursor, CursorMut};
+pub use self::iter::{Iter, Drain, ExtractIf};
+pub use self::compare::{Comparator, Natural, Reverse};
+pub use self::multi::{TreeMultiMap, GetAll};
+pub use self::shift::Shift;
+pub use self::validate::TreeViolation;
+pub use self::stats::Stats;
+pub use self::snapshot::{Pod, SnapshotError};
@@ -0,0 +1,199 @@
+use std::borrow::Borrow;
+
+use arena::{Arena, ArenaFamily, Boxed};
+use tree::{TreeMap, Node, Indirect, Link, Cursor, Comparator, Natural, TreeViolation};
+use tree::rule::Rule;
+
+use super::node::Edge;
+use super::iter::Dismantle;
+use super::validate::validate;
+
+/// Map which keeps every inserted entry, even if their keys are the same.
+///
+/// Entries with the same key are ordered by their insertion.
+pub struct TreeMultiMap<K, V, R, A, I = Link<K, V, R, A>, C = Natural> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    map: TreeMap<K, V, R, A, I, C>,
+}
+
+impl<K, V, R, A, I, C> TreeMultiMap<K, V, R, A, I, C> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K>,
+{
+    pub fn new() -> Self where C: Default, A::Arena<Node<K, V, R, I>>: Default {
+        TreeMultiMap {
+            map: TreeMap::new(),
+        }
+    }
+
+    /// Create an empty map whose keys are ordered by `cmp`.
+    pub fn with_comparator(cmp: C) -> Self where A::Arena<Node<K, V, R, I>>: Default {
+        TreeMultiMap {
+            map: TreeMap::with_comparator(cmp),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Check the invariants of the tree like `TreeMap::validate`, allowing the same keys.
+    pub fn validate(&self) -> Result<(), TreeViolation> {
+        validate(&self.map.root, &self.map.cmp, false)
+    }
+
+    /// Number of all entries, counting each of the same keys.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Insert an entry after all entries with the same key.
+    pub fn insert(&mut self, key: K, value: V) {
+        let index = self.map.root.rank(&key, &self.map.cmp, true);
+        let node = self.map.arena.alloc(Node::new(key, value));
+        self.map.root.insert_at(index, I::new(node));
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Number of entries with the key, in `O(log n)`.
+    pub fn count<Q>(&self, key: &Q) -> usize where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        let root = &self.map.root;
+        root.rank(key, &self.map.cmp, true) - root.rank(key, &self.map.cmp, false)
+    }
+
+    /// Iterate over values with the key, in insertion order.
+    pub fn get_all<Q>(&self, key: &Q) -> GetAll<'_, K, V, R, I> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        let root = &self.map.root;
+
+        GetAll {
+            cursor: Cursor::at(root, root.above(key, &self.map.cmp, true)),
+            remaining: self.count(key),
+        }
+    }
+
+    /// Remove the first inserted entry with the key.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> Option<V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        if !self.contains_key(key) {
+            return None;
+        }
+
+        let index = self.map.root.rank(key, &self.map.cmp, false);
+        self.map.root.remove_at(index).map(|node| Boxed::unbox(node).into_inner().1)
+    }
+
+    /// Remove all entries with the key, returning their values in insertion order.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        let start = self.map.root.rank(key, &self.map.cmp, false);
+        let end = self.map.root.rank(key, &self.map.cmp, true);
+
+        let mut removed = self.map.root.split_at(start);
+        let rest = removed.split_at(end - start);
+        self.map.root.join(rest);
+
+        Dismantle::new(removed).map(|node| Boxed::unbox(node).into_inner().1).collect()
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.map.first_key_value()
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.map.last_key_value()
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.map.pop_first()
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.map.pop_last()
+    }
+
+    /// Cursor pointing the first entry, or the ghost position if the map is empty.
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, R, I> {
+        self.map.cursor_front()
+    }
+
+    /// Cursor pointing the last entry, or the ghost position if the map is empty.
+    pub fn cursor_back(&self) -> Cursor<'_, K, V, R, I> {
+        self.map.cursor_back()
+    }
+}
+
+impl<K, V, R, A, I, C> Default for TreeMultiMap<K, V, R, A, I, C> where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<K, V, R, I>>: Default,
+    I: Indirect<K, V, R, Inner=A::Boxed<Node<K, V, R, I>>>,
+    C: Comparator<K> + Default,
+{
+    fn default() -> Self {
+        TreeMultiMap::new()
+    }
+}
+
+/// Iterator over values with the same key in a `TreeMultiMap`.
+pub struct GetAll<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    cursor: Cursor<'a, K, V, R, I>,
+    remaining: usize,
+}
+
+impl<'a, K, V, R, I> Iterator for GetAll<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let value = self.cursor.value();
+        self.cursor.move_next();
+        self.remaining -= 1;
+
+        value
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, R, I> ExactSizeIterator for GetAll<'a, K, V, R, I> where
+    K: 'a,
+    V: 'a,
+    R: Rule + 'a,
+    I: Indirect<K, V, R> + 'a,
+{}
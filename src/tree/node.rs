@@ -1,178 +1,819 @@
 use std::borrow::Borrow;
-use std::cmp::Ordering::{self, Less, Equal, Greater};
+use std::cell::UnsafeCell;
+use std::cmp::Ordering::{Less, Equal, Greater};
 use std::mem::swap;
+use std::ptr;
 
 use arena::Boxed;
+use tree::Comparator;
 use tree::rule::Rule;
 
+/// Raw pointer to the parent node
+pub type UpLink<K, V, R, I> = <I as Boxed<Node<K, V, R, I>>>::Unsafe;
+
 pub struct Node<K, V, R, I> where
-    K: Ord,
     R: Rule,
     I: Indirect<K, V, R>,
 {
     key: K,
-    value: V,
-    pub up: Option<<I::Inner as Boxed<Node<K, V, R, I>>>::Unsafe>,
+    /// Replaced through shared references when pending tags are pushed down by reads.
+    value: UnsafeCell<V>,
+    size: usize,
+    pub up: Option<UpLink<K, V, R, I>>,
     pub left: Option<I>,
     pub right: Option<I>,
     pub regulator: R,
+    pub aug: I::Aug,
 }
 
 impl<K, V, R, I> Node<K, V, R, I> where
-    K: Ord,
     R: Rule,
     I: Indirect<K, V, R>,
 {
     pub fn new(key: K, value: V) -> Self {
         Node {
             key,
-            value,
+            value: UnsafeCell::new(value),
+            size: 1,
             up: None,
             left: None,
             right: None,
             regulator: R::default(),
+            aug: Default::default(),
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn value(&self) -> &V {
+        unsafe { &*self.value.get() }
+    }
+
+    pub fn value_mut(&mut self) -> &mut V {
+        self.value.get_mut()
+    }
+
+    pub fn key_value_mut(&mut self) -> (&K, &mut V) {
+        (&self.key, self.value.get_mut())
+    }
+
+    pub fn into_inner(self) -> (K, V) {
+        (self.key, self.value.into_inner())
+    }
+
+    /// Replace the value through a shared reference, for `Augment::push_down_shared`.
+    ///
+    /// # Safety
+    ///
+    /// No reference to the value should be alive. It holds for children of a node
+    /// with pending changes, as reads push them down before reaching the children.
+    pub unsafe fn replace_value(&self, value: V) -> V {
+        ptr::replace(self.value.get(), value)
+    }
+
+    /// Number of nodes in the subtree rooted at this node
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Recompute cached data of this node from its children.
+    pub fn refresh(&mut self) {
+        self.size = self.left.len() + self.right.len() + 1;
+        I::Aug::refresh(self);
+    }
+
+    /// Propagate pending changes of this node to its children.
+    pub fn push_down(&mut self) {
+        I::Aug::push_down(self);
+    }
+
+    /// Propagate pending changes of this node to its children, before reads descend into them.
+    pub fn push_down_shared(&self) {
+        I::Aug::push_down_shared(self);
+    }
+}
+
+impl<K, V, R, I> Clone for Node<K, V, R, I> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R> + Clone,
+    I::Aug: Clone,
+{
+    /// Clone the node, sharing its children if `I` does so.
+    ///
+    /// The parent link is not cloned, as the clone has no parent yet.
+    fn clone(&self) -> Self {
+        Node {
+            key: self.key.clone(),
+            value: UnsafeCell::new(self.value().clone()),
+            size: self.size,
+            up: None,
+            left: self.left.clone(),
+            right: self.right.clone(),
+            regulator: self.regulator.clone(),
+            aug: self.aug.clone(),
+        }
+    }
+}
+
+/// Point `up` links of the children to this node.
+///
+/// Children shared with other trees are left as is, as they have no single parent.
+pub fn adopt_children<K, V, R, I>(node: &mut I) where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    let left_owned = node.left.as_ref().is_some_and(|left| !Boxed::is_shared(left));
+    if left_owned {
+        let up = Boxed::to_unsafe(node);
+        if let Some(ref mut left) = node.left {
+            left.up = Some(up);
+        }
+    }
+
+    let right_owned = node.right.as_ref().is_some_and(|right| !Boxed::is_shared(right));
+    if right_owned {
+        let up = Boxed::to_unsafe(node);
+        if let Some(ref mut right) = node.right {
+            right.up = Some(up);
+        }
+    }
+}
+
+/// Build a balanced tree from the first `len` nodes, keeping their order.
+pub fn build_balanced<K, V, R, I, T>(nodes: &mut T, len: usize) -> Option<I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    T: Iterator<Item=I>,
+{
+    if len == 0 {
+        return None;
+    }
+
+    let left_len = len / 2;
+    let left = build_balanced(nodes, left_len);
+
+    let mut node = nodes.next()?;
+    node.left = left;
+    node.right = build_balanced(nodes, len - left_len - 1);
+
+    node.refresh();
+    adopt_children(&mut node);
+    R::build(&mut node);
+
+    Some(node)
+}
+
+/// Copy the tree into new nodes made by `alloc`, keeping its shape and cached data.
+pub fn clone_tree<K, V, R, I, F>(root: &Option<I>, mut alloc: F) -> Option<I> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R>,
+    I::Aug: Clone,
+    F: FnMut(Node<K, V, R, I>) -> I,
+{
+    let mut cloned = None;
+    // Nodes to copy, with the edge to put the copy in and the parent of the edge
+    let mut stack: Vec<(&I, *mut Option<I>, Option<*mut I>)> = Vec::new();
+
+    if let Some(ref root) = *root {
+        stack.push((root, &mut cloned, None));
+    }
+
+    while let Some((node, edge, parent)) = stack.pop() {
+        unsafe {
+            *edge = Some(alloc(Node {
+                key: node.key.clone(),
+                value: UnsafeCell::new(node.value().clone()),
+                size: node.size,
+                up: parent.map(|parent| Boxed::to_unsafe(&mut *parent)),
+                left: None,
+                right: None,
+                regulator: node.regulator.clone(),
+                aug: node.aug.clone(),
+            }));
+
+            // Edges are either the local root or in boxed nodes, which don't move
+            let copy: *mut I = match *edge {
+                Some(ref mut copy) => copy,
+                None => unreachable!(),
+            };
+            let (left, right) = {
+                let copy = &mut *copy;
+                (&mut copy.left as *mut _, &mut copy.right as *mut _)
+            };
+
+            if let Some(ref child) = node.right {
+                stack.push((child, right, Some(copy)));
+            }
+
+            if let Some(ref child) = node.left {
+                stack.push((child, left, Some(copy)));
+            }
         }
     }
+
+    cloned
 }
 
 pub trait Indirect<K, V, R>: Boxed<Node<K, V, R, Self>> where
     Self: Sized,
-    K: Ord,
     R: Rule,
 {
     type Inner: Boxed<Node<K, V, R, Self>>;
+    type Aug: Augment<K, V>;
 
     fn new(inner: Self::Inner) -> Self;
 }
 
+/// Additional data stored in each node, maintained along with the tree structure.
+///
+/// `refresh` is called whenever children of the node have been changed,
+/// and `push_down` is called before children of the node are moved around.
+/// `push_down_shared` is called before reads through shared references descend into
+/// the children, so they see values with pending changes applied.
+/// `check` is only called by validation.
+pub trait Augment<K, V>: Default {
+    fn refresh<R, I>(_node: &mut Node<K, V, R, I>) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {}
+
+    fn push_down<R, I>(_node: &mut Node<K, V, R, I>) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {}
+
+    /// Like `push_down`, but through a shared reference.
+    ///
+    /// Pending changes should be kept in cells to be taken here,
+    /// and values of the children are replaced by `Node::replace_value`.
+    /// Changes which reads see anyway, like pending reversal, may be left as is.
+    fn push_down_shared<R, I>(_node: &Node<K, V, R, I>) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {}
+
+    /// Whether the cached data of the node agrees with its children, for validation.
+    fn check<R, I>(_node: &Node<K, V, R, I>) -> bool where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        true
+    }
+}
+
+impl<K, V> Augment<K, V> for () {}
+
+/// Augmentation which takes tags to be applied to whole subtrees lazily.
+///
+/// `apply` should update what's cached in the node at once,
+/// and leave the rest to be applied to its children by `push_down` and `push_down_shared`.
+pub trait Lazy<K, V>: Augment<K, V> {
+    type Tag;
+
+    fn apply<R, I>(node: &mut Node<K, V, R, I>, tag: &Self::Tag) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>;
+}
+
+/// Update the edges on the path from the deepest one,
+/// after the subtree at its end has been changed.
+///
+/// Each edge should be owned by a node behind the previous one.
+unsafe fn update_path<K, V, R, I>(path: &[*mut Option<I>]) where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    // Rotations in update keep the edges above it in place
+    for &edge in path.iter().rev() {
+        (*edge).update();
+    }
+}
+
 pub trait Edge<K, V, R, I> where
-    K: Ord,
     R: Rule,
     I: Indirect<K, V, R>,
 {
     fn len(&self) -> usize;
-    fn cmp_key<Q>(&self, key: &Q) -> Option<Ordering> where
-        K: Borrow<Q>, Q: Ord + ?Sized;
     fn update(&mut self);
-    fn get<'a, Q>(&'a self, key: &Q) -> Option<&'a V> where
-        K: Borrow<Q> + 'a, Q: Ord + ?Sized, V: 'a, R: 'a;
-    fn get_mut<'a, Q>(&'a mut self, key: &Q) -> Option<&'a mut V> where
-        K: Borrow<Q> + 'a, Q: Ord + ?Sized, V: 'a, R: 'a;
-    fn remove<Q>(&mut self, key: &Q) -> Option<V> where
-        K: Borrow<Q>, Q: Ord + ?Sized;
-    fn insert(&mut self, node: I) -> Option<V>;
+    fn get<'a, Q, C>(&'a self, key: &Q, cmp: &C) -> Option<&'a V> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a;
+    fn get_mut<'a, Q, C>(&'a mut self, key: &Q, cmp: &C) -> Option<&'a mut V> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a;
+    fn remove<Q, C>(&mut self, key: &Q, cmp: &C) -> Option<V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>;
+    fn insert<C>(&mut self, node: I, cmp: &C) -> Option<V> where C: Comparator<K>;
+
+    fn first<'a>(&'a self) -> Option<&'a Node<K, V, R, I>> where
+        K: 'a, V: 'a, R: 'a;
+    fn last<'a>(&'a self) -> Option<&'a Node<K, V, R, I>> where
+        K: 'a, V: 'a, R: 'a;
+    /// Find the node with the greatest key less than (or equal to) the key.
+    ///
+    /// Among nodes with the same key, the last one is found.
+    fn below<'a, Q, C>(&'a self, key: &Q, cmp: &C, inclusive: bool) -> Option<&'a Node<K, V, R, I>> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a;
+    /// Find the node with the least key greater than (or equal to) the key.
+    ///
+    /// Among nodes with the same key, the first one is found.
+    fn above<'a, Q, C>(&'a self, key: &Q, cmp: &C, inclusive: bool) -> Option<&'a Node<K, V, R, I>> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a;
+    /// Count nodes with keys less than (or equal to) the key.
+    fn rank<Q, C>(&self, key: &Q, cmp: &C, inclusive: bool) -> usize where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>;
+
+    /// Detach the top node of this edge, replacing it with its children joined.
+    fn detach(&mut self) -> Option<I>;
+    /// Detach the first node in this subtree.
+    fn pop_first(&mut self) -> Option<I>;
+    /// Detach the last node in this subtree.
+    fn pop_last(&mut self) -> Option<I>;
+    /// Append all nodes of `right` after the nodes of this subtree.
+    fn join(&mut self, right: Option<I>);
+    /// Keep first `index` nodes in this subtree, and return the rest.
+    fn split_at(&mut self, index: usize) -> Option<I>;
+    /// Split this subtree, keeping nodes with smaller keys and returning
+    /// the node with the key and the subtree with greater keys.
+    fn split_key<Q, C>(&mut self, key: &Q, cmp: &C) -> (Option<I>, Option<I>) where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>;
+    /// Join with the middle node and the right subtree, whose keys are all greater.
+    ///
+    /// The joined subtree has no parent.
+    fn join_with(&mut self, mid: I, right: Option<I>);
+    fn insert_at(&mut self, index: usize, node: I);
+    fn remove_at(&mut self, index: usize) -> Option<I>;
+    /// Drop all nodes in this subtree, without recursion.
+    fn clear(&mut self);
 }
 
 impl<K, V, R, I> Edge<K, V, R, I> for Option<I> where
-    K: Ord,
     R: Rule,
     I: Indirect<K, V, R>,
 {
     fn len(&self) -> usize {
         match *self {
             None => 0,
-            Some(ref node) => node.left.len() + node.right.len() + 1,
+            Some(ref node) => node.size,
+        }
+    }
+
+    fn update(&mut self) {
+        if let Some(ref mut node) = *self {
+            node.refresh();
+            adopt_children(node);
+            R::update(node);
         }
     }
 
-    fn cmp_key<Q>(&self, key: &Q) -> Option<Ordering> where
-        K: Borrow<Q>, Q: Ord + ?Sized
+    fn get<'a, Q, C>(&'a self, key: &Q, cmp: &C) -> Option<&'a V> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a
     {
-        match *self {
-            None => None,
-            Some(ref node) => {
-                if node.key.borrow() == key {
-                    Some(Equal)
-                } else if node.key.borrow() <= key {
-                    Some(Less)
-                } else {
-                    Some(Greater)
+        let mut edge = self;
+
+        loop {
+            let node = edge.as_ref()?;
+
+            match cmp.compare(key, node.key.borrow()) {
+                Equal => return Some(node.value()),
+                Less => edge = &node.left,
+                Greater => edge = &node.right,
+            }
+
+            node.push_down_shared();
+        }
+    }
+
+    fn get_mut<'a, Q, C>(&'a mut self, key: &Q, cmp: &C) -> Option<&'a mut V> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a
+    {
+        let mut edge = self;
+
+        loop {
+            let node = edge.as_mut()?;
+            node.push_down();
+
+            match cmp.compare(key, node.key.borrow()) {
+                Equal => return Some(node.value.get_mut()),
+                Less => edge = &mut node.left,
+                Greater => edge = &mut node.right,
+            }
+        }
+    }
+
+    fn remove<Q, C>(&mut self, key: &Q, cmp: &C) -> Option<V> where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        let mut path = Vec::new();
+        let mut edge: *mut Option<I> = self;
+
+        unsafe {
+            loop {
+                let node = (*edge).as_mut()?;
+                node.push_down();
+
+                match cmp.compare(key, node.key.borrow()) {
+                    Equal => break,
+                    Less => {
+                        path.push(edge);
+                        edge = &mut node.left;
+                    }
+                    Greater => {
+                        path.push(edge);
+                        edge = &mut node.right;
+                    }
                 }
             }
+
+            let node = (*edge).detach();
+            update_path(&path);
+
+            node.map(|node| Boxed::unbox(node).value.into_inner())
         }
     }
 
-    fn update(&mut self) {
-        if let Some(ref mut node) = *self {
-            R::update(node);
+    fn insert<C>(&mut self, mut newbie: I, cmp: &C) -> Option<V> where C: Comparator<K> {
+        let mut path = Vec::new();
+        let mut edge: *mut Option<I> = self;
+
+        unsafe {
+            while let Some(ref mut node) = *edge {
+                node.push_down();
+
+                match cmp.compare(&newbie.key, &node.key) {
+                    Equal => {
+                        swap(&mut node.key, &mut newbie.key);
+                        swap(node.value.get_mut(), newbie.value.get_mut());
+                        return Some(Boxed::unbox(newbie).value.into_inner());
+                    }
+                    Less => {
+                        path.push(edge);
+                        edge = &mut node.left;
+                    }
+                    Greater => {
+                        path.push(edge);
+                        edge = &mut node.right;
+                    }
+                }
+            }
+
+            // A new node is added to the subtree
+            *edge = Some(newbie);
+            update_path(&path);
         }
+
+        None
     }
 
-    fn get<'a, Q>(&'a self, key: &Q) -> Option<&'a V> where
-        K: Borrow<Q> + 'a, Q: Ord + ?Sized, V: 'a, R: 'a
+    fn first<'a>(&'a self) -> Option<&'a Node<K, V, R, I>> where
+        K: 'a, V: 'a, R: 'a
     {
-        match self.cmp_key(key) {
-            None => None,
-            Some(ord) => self.as_ref().and_then(|node| match ord {
-                Equal => Some(&node.value),
-                Less => node.left.get(key),
-                Greater => node.right.get(key),
-            }),
+        let mut node = &**self.as_ref()?;
+        node.push_down_shared();
+
+        while let Some(ref left) = node.left {
+            node = left;
+            node.push_down_shared();
         }
+
+        Some(node)
     }
 
-    fn get_mut<'a, Q>(&'a mut self, key: &Q) -> Option<&'a mut V> where
-        K: Borrow<Q> + 'a, Q: Ord + ?Sized, V: 'a, R: 'a
+    fn last<'a>(&'a self) -> Option<&'a Node<K, V, R, I>> where
+        K: 'a, V: 'a, R: 'a
     {
-        match self.cmp_key(key) {
-            None => None,
-            Some(ord) => self.as_mut().and_then(|node| match ord {
-                Equal => Some(&mut node.value),
-                Less => node.left.get_mut(key),
-                Greater => node.right.get_mut(key),
-            }),
+        let mut node = &**self.as_ref()?;
+        node.push_down_shared();
+
+        while let Some(ref right) = node.right {
+            node = right;
+            node.push_down_shared();
         }
+
+        Some(node)
+    }
+
+    fn below<'a, Q, C>(&'a self, key: &Q, cmp: &C, inclusive: bool) -> Option<&'a Node<K, V, R, I>> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a
+    {
+        let mut edge = self;
+        let mut found = None;
+
+        while let Some(ref node) = *edge {
+            node.push_down_shared();
+
+            match cmp.compare(key, node.key.borrow()) {
+                Less => edge = &node.left,
+                Equal if !inclusive => edge = &node.left,
+                Greater | Equal => {
+                    found = Some(&**node);
+                    edge = &node.right;
+                }
+            }
+        }
+
+        found
     }
 
-    fn remove<Q>(&mut self, key: &Q) -> Option<V> where
-        K: Borrow<Q>, Q: Ord + ?Sized
+    fn above<'a, Q, C>(&'a self, key: &Q, cmp: &C, inclusive: bool) -> Option<&'a Node<K, V, R, I>> where
+        K: Borrow<Q> + 'a, Q: ?Sized, C: Comparator<Q>, V: 'a, R: 'a
     {
-        let child = match self.cmp_key(key) {
-            // This node is empty leaf
-            None => return None,
-            // This node matches the key, so removed
-            Some(Equal) => {
-                return self.take().map(|node| Boxed::unbox(node).value)
-            }
-            Some(ord) => self.as_mut().and_then(|node| match ord {
-                Equal => unreachable!(),
-                Less => node.left.remove(key),
-                Greater => node.right.remove(key),
-            }),
-        };
-
-        // A child node has removed
-        if child.is_some() {
-            self.update();
-        }
-
-        child
-    }
-
-    fn insert(&mut self, mut newbie: I) -> Option<V> {
-        let res = match *self {
-            None => {
-                *self = Some(newbie);
-                return None
-            }
-            Some(ref mut node) => {
-                if node.key == newbie.key {
-                    swap(&mut node.key, &mut newbie.key);
-                    swap(&mut node.value, &mut newbie.value);
-                    Some(Boxed::unbox(newbie).value)
-                } else if node.key <= newbie.key {
-                    node.left.insert(newbie)
+        let mut edge = self;
+        let mut found = None;
+
+        while let Some(ref node) = *edge {
+            node.push_down_shared();
+
+            match cmp.compare(key, node.key.borrow()) {
+                Greater => edge = &node.right,
+                Equal if !inclusive => edge = &node.right,
+                Less | Equal => {
+                    found = Some(&**node);
+                    edge = &node.left;
+                }
+            }
+        }
+
+        found
+    }
+
+    fn rank<Q, C>(&self, key: &Q, cmp: &C, inclusive: bool) -> usize where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        let mut edge = self;
+        let mut rank = 0;
+
+        while let Some(ref node) = *edge {
+            match cmp.compare(key, node.key.borrow()) {
+                Less => edge = &node.left,
+                Equal if !inclusive => edge = &node.left,
+                Greater | Equal => {
+                    rank += node.left.len() + 1;
+                    edge = &node.right;
+                }
+            }
+        }
+
+        rank
+    }
+
+    fn detach(&mut self) -> Option<I> {
+        let mut node = self.take()?;
+        node.push_down();
+        let up = node.up.take();
+
+        *self = node.left.take();
+        self.join(node.right.take());
+        node.refresh();
+
+        if let Some(ref mut top) = *self {
+            top.up = up;
+        }
+
+        Some(node)
+    }
+
+    fn pop_first(&mut self) -> Option<I> {
+        let mut path = Vec::new();
+        let mut edge: *mut Option<I> = self;
+
+        unsafe {
+            loop {
+                let node = (*edge).as_mut()?;
+                node.push_down();
+
+                if node.left.is_none() {
+                    break;
+                }
+
+                path.push(edge);
+                edge = &mut node.left;
+            }
+
+            let first = (*edge).detach();
+            update_path(&path);
+
+            first
+        }
+    }
+
+    fn pop_last(&mut self) -> Option<I> {
+        let mut path = Vec::new();
+        let mut edge: *mut Option<I> = self;
+
+        unsafe {
+            loop {
+                let node = (*edge).as_mut()?;
+                node.push_down();
+
+                if node.right.is_none() {
+                    break;
+                }
+
+                path.push(edge);
+                edge = &mut node.right;
+            }
+
+            let last = (*edge).detach();
+            update_path(&path);
+
+            last
+        }
+    }
+
+    fn join(&mut self, right: Option<I>) {
+        if right.is_none() {
+            return;
+        }
+
+        let up = self.as_mut().and_then(|node| node.up.take());
+
+        // Use the last node of the left subtree as a new root
+        match self.pop_last() {
+            None => *self = right,
+            Some(mut mid) => {
+                mid.left = self.take();
+                mid.right = right;
+                *self = Some(mid);
+                self.update();
+            }
+        }
+
+        if let Some(ref mut top) = *self {
+            top.up = up;
+        }
+    }
+
+    fn split_at(&mut self, mut index: usize) -> Option<I> {
+        let mut rest = None;
+        let mut rest_edge: *mut Option<I> = &mut rest;
+        let mut head_edge: *mut Option<I> = self;
+        let mut path = Vec::new();
+
+        let mut subtree = self.take();
+        let up = subtree.as_mut().and_then(|node| node.up.take());
+
+        unsafe {
+            // Hand over each node on the path to either of the two trees
+            while let Some(mut node) = subtree {
+                node.push_down();
+                let left_len = node.left.len();
+
+                if index > left_len {
+                    index -= left_len + 1;
+                    subtree = node.right.take();
+                    *head_edge = Some(node);
+                    path.push(head_edge);
+                    head_edge = &mut (*head_edge).as_mut().unwrap().right;
                 } else {
-                    node.right.insert(newbie)
+                    subtree = node.left.take();
+                    *rest_edge = Some(node);
+                    path.push(rest_edge);
+                    rest_edge = &mut (*rest_edge).as_mut().unwrap().left;
                 }
             }
-        };
 
-        // when a new node is added to the subtree
-        if res.is_none() {
-            self.update();
+            update_path(&path);
+        }
+
+        if let Some(ref mut head) = *self {
+            head.up = up;
+        }
+
+        if let Some(ref mut rest) = rest {
+            rest.up = None;
         }
 
-        res
+        rest
     }
+
+    fn split_key<Q, C>(&mut self, key: &Q, cmp: &C) -> (Option<I>, Option<I>) where
+        K: Borrow<Q>, Q: ?Sized, C: Comparator<Q>
+    {
+        let mut rest = None;
+        let mut found = None;
+        let mut rest_edge: *mut Option<I> = &mut rest;
+        let mut head_edge: *mut Option<I> = self;
+        let mut path = Vec::new();
+
+        let mut subtree = self.take();
+        let up = subtree.as_mut().and_then(|node| node.up.take());
+
+        unsafe {
+            while let Some(mut node) = subtree {
+                node.push_down();
+
+                match cmp.compare(key, node.key.borrow()) {
+                    Greater => {
+                        subtree = node.right.take();
+                        *head_edge = Some(node);
+                        path.push(head_edge);
+                        head_edge = &mut (*head_edge).as_mut().unwrap().right;
+                    }
+                    Less => {
+                        subtree = node.left.take();
+                        *rest_edge = Some(node);
+                        path.push(rest_edge);
+                        rest_edge = &mut (*rest_edge).as_mut().unwrap().left;
+                    }
+                    Equal => {
+                        *head_edge = node.left.take();
+                        *rest_edge = node.right.take();
+                        node.up = None;
+                        node.refresh();
+                        found = Some(node);
+                        break;
+                    }
+                }
+            }
+
+            update_path(&path);
+        }
+
+        if let Some(ref mut head) = *self {
+            head.up = up;
+        }
+
+        if let Some(ref mut rest) = rest {
+            rest.up = None;
+        }
+
+        (found, rest)
+    }
+
+    fn join_with(&mut self, mut mid: I, right: Option<I>) {
+        mid.up = None;
+        mid.left = self.take();
+        mid.right = right;
+        *self = Some(mid);
+        self.update();
+    }
+
+    fn insert_at(&mut self, mut index: usize, newbie: I) {
+        let mut path = Vec::new();
+        let mut edge: *mut Option<I> = self;
+
+        unsafe {
+            while let Some(ref mut node) = *edge {
+                node.push_down();
+                let left_len = node.left.len();
+                path.push(edge);
+
+                if index <= left_len {
+                    edge = &mut node.left;
+                } else {
+                    index -= left_len + 1;
+                    edge = &mut node.right;
+                }
+            }
+
+            *edge = Some(newbie);
+            update_path(&path);
+        }
+    }
+
+    fn remove_at(&mut self, mut index: usize) -> Option<I> {
+        let mut path = Vec::new();
+        let mut edge: *mut Option<I> = self;
+
+        unsafe {
+            loop {
+                let node = (*edge).as_mut()?;
+                node.push_down();
+                let left_len = node.left.len();
+
+                match index.cmp(&left_len) {
+                    Equal => break,
+                    Less => {
+                        path.push(edge);
+                        edge = &mut node.left;
+                    }
+                    Greater => {
+                        index -= left_len + 1;
+                        path.push(edge);
+                        edge = &mut node.right;
+                    }
+                }
+            }
+
+            let node = (*edge).detach();
+            update_path(&path);
+
+            node
+        }
+    }
+
+    fn clear(&mut self) {
+        let mut stack: Vec<I> = self.take().into_iter().collect();
+
+        while let Some(mut node) = stack.pop() {
+            // Shared nodes are only released, not dropped
+            if Boxed::is_shared(&node) {
+                continue;
+            }
+
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+
 }
@@ -0,0 +1,206 @@
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use arena::{Arena, Boxed};
+use arena::rc_arena::{RcArena, Boxed as RcBoxed};
+use tree::{Node, Indirect, Augment, Natural};
+use tree::rule::Rule;
+
+use super::node::Edge;
+
+/// Box of a node shared between versions of a `PersistentMap`, with augmentation `G`.
+///
+/// The `up` links of its nodes may dangle: a child shared by two versions keeps the link
+/// to its parent in one of them, which may be dropped first. They are never dereferenced though.
+/// Only cursors follow `up` links, and they're made by maps over an `ArenaFamily`,
+/// which `RcArena` is not, so no cursor can hold `RcLink` nodes.
+/// `Edge` and the `Rotate` impls only move the links between nodes.
+pub struct RcLink<K, V, R, G = ()>(RcBoxed<Node<K, V, R, Self>>) where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    G: Augment<K, V> + Clone;
+
+impl<K, V, R, G> Clone for RcLink<K, V, R, G> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    G: Augment<K, V> + Clone,
+{
+    fn clone(&self) -> Self {
+        RcLink(self.0.clone())
+    }
+}
+
+impl<K, V, R, G> Deref for RcLink<K, V, R, G> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    G: Augment<K, V> + Clone,
+{
+    type Target = Node<K, V, R, Self>;
+
+    fn deref(&self) -> &Node<K, V, R, Self> {
+        &self.0
+    }
+}
+
+impl<K, V, R, G> DerefMut for RcLink<K, V, R, G> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    G: Augment<K, V> + Clone,
+{
+    fn deref_mut(&mut self) -> &mut Node<K, V, R, Self> {
+        &mut self.0
+    }
+}
+
+impl<K, V, R, G> Boxed<Node<K, V, R, Self>> for RcLink<K, V, R, G> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    G: Augment<K, V> + Clone,
+{
+    type Unsafe = <RcBoxed<Node<K, V, R, Self>> as Boxed<Node<K, V, R, Self>>>::Unsafe;
+
+    fn unbox(boxed: Self) -> Node<K, V, R, Self> {
+        Boxed::unbox(boxed.0)
+    }
+
+    fn to_unsafe(boxed: &mut Self) -> Self::Unsafe {
+        Boxed::to_unsafe(&mut boxed.0)
+    }
+
+    fn is_shared(boxed: &Self) -> bool {
+        Boxed::is_shared(&boxed.0)
+    }
+}
+
+impl<K, V, R, G> Indirect<K, V, R> for RcLink<K, V, R, G> where
+    K: Clone,
+    V: Clone,
+    R: Rule + Clone,
+    G: Augment<K, V> + Clone,
+{
+    type Inner = RcBoxed<Node<K, V, R, Self>>;
+    type Aug = G;
+
+    fn new(inner: Self::Inner) -> Self {
+        RcLink(inner)
+    }
+}
+
+/// Immutable map whose versions share unchanged subtrees with each other.
+///
+/// Each modification returns a new version of the map,
+/// copying only the nodes on the path from the root to the modified node.
+/// Cloning the map takes `O(1)`.
+pub struct PersistentMap<K, V, R, I = RcLink<K, V, R>> where
+    K: Ord + Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R, Inner=RcBoxed<Node<K, V, R, I>>> + Clone,
+    I::Aug: Clone,
+{
+    arena: RcArena<Node<K, V, R, I>>,
+    root: Option<I>,
+    _marker: PhantomData<Node<K, V, R, I>>,
+}
+
+impl<K, V, R, I> PersistentMap<K, V, R, I> where
+    K: Ord + Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R, Inner=RcBoxed<Node<K, V, R, I>>> + Clone,
+    I::Aug: Clone,
+{
+    pub fn new() -> Self {
+        PersistentMap {
+            arena: RcArena::new(),
+            root: None,
+            _marker: Default::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V> where
+        K: Borrow<Q>, Q: Ord + ?Sized
+    {
+        self.root.get(key, &Natural)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool where
+        K: Borrow<Q>, Q: Ord + ?Sized
+    {
+        self.root.get(key, &Natural).is_some()
+    }
+
+    /// Return a new version of the map with the key inserted.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let mut next = self.clone();
+        let node = next.arena.alloc(Node::new(key, value));
+        Edge::insert(&mut next.root, I::new(node), &Natural);
+
+        next
+    }
+
+    /// Return a new version of the map without the key.
+    pub fn remove<Q>(&self, key: &Q) -> Self where
+        K: Borrow<Q>, Q: Ord + ?Sized
+    {
+        let mut next = self.clone();
+        next.root.remove(key, &Natural);
+
+        next
+    }
+}
+
+impl<K, V, R, I> Clone for PersistentMap<K, V, R, I> where
+    K: Ord + Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R, Inner=RcBoxed<Node<K, V, R, I>>> + Clone,
+    I::Aug: Clone,
+{
+    fn clone(&self) -> Self {
+        PersistentMap {
+            arena: self.arena.clone(),
+            root: self.root.clone(),
+            _marker: Default::default(),
+        }
+    }
+}
+
+impl<K, V, R, I> Default for PersistentMap<K, V, R, I> where
+    K: Ord + Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R, Inner=RcBoxed<Node<K, V, R, I>>> + Clone,
+    I::Aug: Clone,
+{
+    fn default() -> Self {
+        PersistentMap::new()
+    }
+}
+
+impl<K, V, R, I> Drop for PersistentMap<K, V, R, I> where
+    K: Ord + Clone,
+    V: Clone,
+    R: Rule + Clone,
+    I: Indirect<K, V, R, Inner=RcBoxed<Node<K, V, R, I>>> + Clone,
+    I::Aug: Clone,
+{
+    fn drop(&mut self) {
+        // Dropping nodes recursively may overflow the stack on deep trees
+        self.root.clear();
+    }
+}
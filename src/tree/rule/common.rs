@@ -1,19 +1,45 @@
 use std::mem::replace;
 
-use tree::Indirect;
+use tree::{Node, Indirect};
+use tree::node::adopt_children;
 
 pub trait Rule: Default {
+    /// Restore the balance of the subtree, after its children have been changed.
+    ///
+    /// Children of the node are already balanced when this is called.
     fn update<K, V, I>(node: &mut I) where
-        K: Ord,
         I: Indirect<K, V, Self>;
+
+    /// Initialize the regulator of the node in a tree built at once, keeping its shape.
+    ///
+    /// Children of the node are already built when this is called.
+    fn build<K, V, I>(_node: &mut I) where
+        I: Indirect<K, V, Self>
+    {}
+
+    /// Check the invariant of this rule at the node, for validation.
+    ///
+    /// Children of the node are checked separately, so only the relation
+    /// between the node and its children needs to be checked.
+    fn check<K, V, I>(_node: &Node<K, V, Self, I>) -> Result<(), &'static str> where
+        I: Indirect<K, V, Self>
+    {
+        Ok(())
+    }
+
+    /// Whether the node should be placed above the other one, when two trees are merged.
+    ///
+    /// By default, nodes of the first tree are placed above.
+    fn outranks(&self, _other: &Self) -> bool {
+        false
+    }
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Noop;
 
 impl Rule for Noop {
     fn update<K, V, I>(_node: &mut I) where
-        K: Ord,
         I: Indirect<K, V, Self>
     {}
 }
@@ -23,14 +49,14 @@ pub struct RotateEmptyLeg;
 
 pub trait Rotate {
     fn rotate_left<K, V, R>(&mut self) -> Result<(), RotateEmptyLeg> where
-        K: Ord, R: Rule, Self: Indirect<K, V, R>;
+        R: Rule, Self: Indirect<K, V, R>;
     fn rotate_right<K, V, R>(&mut self) -> Result<(), RotateEmptyLeg> where
-        K: Ord, R: Rule, Self: Indirect<K, V, R>;
+        R: Rule, Self: Indirect<K, V, R>;
 }
 
 impl<T> Rotate for T {
     fn rotate_left<K, V, R>(&mut self) -> Result<(), RotateEmptyLeg> where
-        K: Ord, R: Rule, Self: Indirect<K, V, R>
+        R: Rule, Self: Indirect<K, V, R>
     {
         //
         //     R            B
@@ -40,24 +66,32 @@ impl<T> Rotate for T {
         //     C   D    A   C
         //
 
+        self.push_down();
+
         let node_b = match self.right.take() {
             None => Err(RotateEmptyLeg)?,
             Some(node) => node,
         };
 
         let mut node_r = replace(self, node_b);
+        self.push_down();
+        self.up = node_r.up.take();
 
         let edge_c = self.left.take();
 
         node_r.right = edge_c;
+        node_r.refresh();
+        adopt_children::<K, V, R, Self>(&mut node_r);
 
         self.left = Some(node_r);
+        self.refresh();
+        adopt_children::<K, V, R, Self>(self);
 
         Ok(())
     }
 
     fn rotate_right<K, V, R>(&mut self) -> Result<(), RotateEmptyLeg> where
-        K: Ord, R: Rule, Self: Indirect<K, V, R>
+        R: Rule, Self: Indirect<K, V, R>
     {
         //
         //     R            A
@@ -67,18 +101,26 @@ impl<T> Rotate for T {
         // C   D            D   B
         //
 
+        self.push_down();
+
         let node_a = match self.left.take() {
             None => Err(RotateEmptyLeg)?,
             Some(node) => node,
         };
 
         let mut node_r = replace(self, node_a);
+        self.push_down();
+        self.up = node_r.up.take();
 
         let edge_d = self.right.take();
 
         node_r.left = edge_d;
+        node_r.refresh();
+        adopt_children::<K, V, R, Self>(&mut node_r);
 
         self.right = Some(node_r);
+        self.refresh();
+        adopt_children::<K, V, R, Self>(self);
 
         Ok(())
     }
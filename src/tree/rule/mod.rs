@@ -4,7 +4,7 @@ mod rev_treap;
 
 pub mod prelude {
     pub use super::common::{Rule, Rotate};
-    pub use tree::Indirect;
+    pub use tree::{Node, Indirect};
 }
 
 pub use self::common::{Rule, Noop};
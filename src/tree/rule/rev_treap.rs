@@ -1,3 +1,5 @@
+use std::mem::swap;
+
 use rand::random;
 
 use super::prelude::*;
@@ -14,33 +16,89 @@ impl Default for RevTreap {
 
 impl Rule for RevTreap {
     fn update<K, V, I>(node: &mut I) where
-        K: Ord,
         I: Indirect<K, V, Self>,
     {
-        let root = node.regulator.0;
-
         enum Dir {
             Left, Right, Nope
         }
 
-        let mut dir = Dir::Nope;
+        let mut node = node;
+
+        // Sift down the node until both children have lower priority
+        loop {
+            let mut top = node.regulator.0;
+            let mut dir = Dir::Nope;
 
-        if let Some(ref left) = node.left {
-            if left.regulator.0 > root {
-                dir = Dir::Left;
+            if let Some(ref left) = node.left {
+                if left.regulator.0 > top {
+                    top = left.regulator.0;
+                    dir = Dir::Left;
+                }
             }
-        }
 
-        if let Some(ref right) = node.right {
-            if right.regulator.0 > root {
-                dir = Dir::Right;
+            if let Some(ref right) = node.right {
+                if right.regulator.0 > top {
+                    dir = Dir::Right;
+                }
             }
+
+            node = match dir {
+                Dir::Left => {
+                    node.rotate_right().unwrap();
+                    match node.right {
+                        Some(ref mut right) => right,
+                        None => unreachable!(),
+                    }
+                }
+                Dir::Right => {
+                    node.rotate_left().unwrap();
+                    match node.left {
+                        Some(ref mut left) => left,
+                        None => unreachable!(),
+                    }
+                }
+                Dir::Nope => return,
+            };
+        }
+    }
+
+    fn build<K, V, I>(node: &mut I) where
+        I: Indirect<K, V, Self>,
+    {
+        let mut node = &mut **node;
+
+        // Sift down the priority, as building a binary heap
+        loop {
+            let left = node.left.as_ref().map_or(0, |left| left.regulator.0);
+            let right = node.right.as_ref().map_or(0, |right| right.regulator.0);
+
+            let child = if left > node.regulator.0 && left >= right {
+                node.left.as_mut()
+            } else if right > node.regulator.0 {
+                node.right.as_mut()
+            } else {
+                return;
+            };
+
+            let child = &mut **child.unwrap();
+            swap(&mut node.regulator, &mut child.regulator);
+            node = child;
         }
+    }
+
+    fn check<K, V, I>(node: &Node<K, V, Self, I>) -> Result<(), &'static str> where
+        I: Indirect<K, V, Self>,
+    {
+        let mut children = node.left.iter().chain(node.right.iter());
 
-        match dir {
-            Dir::Left => node.rotate_right().unwrap(),
-            Dir::Right => node.rotate_left().unwrap(),
-            _ => {}
+        if children.any(|child| child.regulator.0 > node.regulator.0) {
+            return Err("child has higher priority than its parent");
         }
+
+        Ok(())
+    }
+
+    fn outranks(&self, other: &Self) -> bool {
+        self.0 > other.0
     }
 }
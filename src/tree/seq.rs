@@ -0,0 +1,228 @@
+use std::marker::PhantomData;
+use std::mem::swap;
+use std::ops::Range;
+
+use arena::{Arena, ArenaFamily, Boxed};
+use tree::{Node, Indirect, Link, Augment, Lazy, Natural, TreeViolation};
+use tree::rule::Rule;
+
+use super::node::Edge;
+use super::validate::validate;
+
+/// Pending reversal of the subtree, propagated lazily.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Flip(bool);
+
+impl<K, V> Augment<K, V> for Flip {
+    fn push_down<R, I>(node: &mut Node<K, V, R, I>) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        if !node.aug.0 {
+            return;
+        }
+
+        node.aug.0 = false;
+        swap(&mut node.left, &mut node.right);
+
+        if let Some(ref mut left) = node.left {
+            Flip::apply(left, &());
+        }
+
+        if let Some(ref mut right) = node.right {
+            Flip::apply(right, &());
+        }
+    }
+}
+
+impl<K, V> Lazy<K, V> for Flip {
+    type Tag = ();
+
+    fn apply<R, I>(node: &mut Node<K, V, R, I>, _: &()) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        node.aug.0 ^= true;
+    }
+}
+
+/// Sequence of values indexed by its position, backed by a tree.
+///
+/// Unlike `Vec`, insertion and removal at arbitrary position takes `O(log n)`
+/// given that the rule keeps the tree balanced.
+pub struct TreeSeq<T, R, A, I = Link<(), T, R, A, Flip>> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<(), T, R, Aug=Flip, Inner=A::Boxed<Node<(), T, R, I>>>,
+{
+    arena: A::Arena<Node<(), T, R, I>>,
+    root: Option<I>,
+    _marker: PhantomData<Node<(), T, R, I>>,
+}
+
+impl<T, R, A, I> TreeSeq<T, R, A, I> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<(), T, R, Aug=Flip, Inner=A::Boxed<Node<(), T, R, I>>>,
+{
+    pub fn new() -> Self where A::Arena<Node<(), T, R, I>>: Default {
+        TreeSeq {
+            arena: Default::default(),
+            root: None,
+            _marker: Default::default(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.root.clear();
+    }
+
+    /// Check the invariants of the tree like `TreeMap::validate`, except the order of values.
+    pub fn validate(&self) -> Result<(), TreeViolation> {
+        validate(&self.root, &Natural, false)
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn get(&self, mut index: usize) -> Option<&T> {
+        let mut edge = &self.root;
+        // Pending reversals are accumulated instead of pushed down
+        let mut flip = false;
+
+        while let Some(ref node) = *edge {
+            flip ^= node.aug.0;
+
+            let (left, right) = if flip {
+                (&node.right, &node.left)
+            } else {
+                (&node.left, &node.right)
+            };
+            let left_len = left.len();
+
+            if index < left_len {
+                edge = left;
+            } else if index == left_len {
+                return Some(node.value());
+            } else {
+                index -= left_len + 1;
+                edge = right;
+            }
+        }
+
+        None
+    }
+
+    pub fn get_mut(&mut self, mut index: usize) -> Option<&mut T> {
+        let mut edge = &mut self.root;
+
+        loop {
+            let node = edge.as_mut()?;
+            node.push_down();
+            let left_len = node.left.len();
+
+            if index < left_len {
+                edge = &mut node.left;
+            } else if index == left_len {
+                return Some(node.value_mut());
+            } else {
+                index -= left_len + 1;
+                edge = &mut node.right;
+            }
+        }
+    }
+
+    /// Insert a value at `index`, shifting all values after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_at(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(index <= len, "insertion index (is {}) should be <= len (is {})", index, len);
+
+        let node = self.arena.alloc(Node::new((), value));
+        self.root.insert_at(index, I::new(node));
+    }
+
+    pub fn push(&mut self, value: T) {
+        let len = self.len();
+        self.insert_at(len, value);
+    }
+
+    /// Remove and return the value at `index`, shifting all values after it.
+    pub fn remove_at(&mut self, index: usize) -> Option<T> {
+        self.root.remove_at(index).map(|node| Boxed::unbox(node).into_inner().1)
+    }
+
+    /// Split the sequence into two at `index`.
+    ///
+    /// `self` keeps values in `[0, index)`, and the returned sequence
+    /// takes values in `[index, len)`. Both share the same arena.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn split_at(&mut self, index: usize) -> Self where A::Arena<Node<(), T, R, I>>: Clone {
+        let len = self.len();
+        assert!(index <= len, "split index (is {}) should be <= len (is {})", index, len);
+
+        TreeSeq {
+            arena: self.arena.clone(),
+            root: self.root.split_at(index),
+            _marker: Default::default(),
+        }
+    }
+
+    /// Append all values of `other` after the values of `self`.
+    pub fn concat(&mut self, mut other: Self) {
+        self.root.join(other.root.take());
+    }
+
+    /// Reverse the order of values in `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is decreasing or out of bounds.
+    pub fn reverse(&mut self, range: Range<usize>) {
+        let len = self.len();
+        assert!(range.start <= range.end, "range start (is {}) should be <= end (is {})",
+            range.start, range.end);
+        assert!(range.end <= len, "range end (is {}) should be <= len (is {})", range.end, len);
+
+        let mut mid = self.root.split_at(range.start);
+        let rest = mid.split_at(range.end - range.start);
+
+        if let Some(ref mut node) = mid {
+            Flip::apply(node, &());
+        }
+
+        self.root.join(mid);
+        self.root.join(rest);
+    }
+}
+
+impl<T, R, A, I> Default for TreeSeq<T, R, A, I> where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<(), T, R, I>>: Default,
+    I: Indirect<(), T, R, Aug=Flip, Inner=A::Boxed<Node<(), T, R, I>>>,
+{
+    fn default() -> Self {
+        TreeSeq::new()
+    }
+}
+
+impl<T, R, A, I> Drop for TreeSeq<T, R, A, I> where
+    R: Rule,
+    A: ArenaFamily,
+    I: Indirect<(), T, R, Aug=Flip, Inner=A::Boxed<Node<(), T, R, I>>>,
+{
+    fn drop(&mut self) {
+        // Dropping nodes recursively may overflow the stack on deep trees
+        self.root.clear();
+    }
+}
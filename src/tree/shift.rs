@@ -0,0 +1,87 @@
+use std::cell::Cell;
+use std::fmt;
+use std::ops::Add;
+
+use tree::{Node, Indirect, Augment, Lazy};
+use tree::rule::Rule;
+
+/// Pending addition to all values in the subtree, propagated lazily.
+///
+/// Use it as the augmentation of a `TreeMap` to shift values of a key range
+/// by `update_range`.
+pub struct Shift<V>(Cell<Option<V>>);
+
+impl<V> Shift<V> where V: Clone + Add<Output=V> {
+    /// Add the delta to the value of the node, and to the pending one for its children.
+    ///
+    /// # Safety
+    ///
+    /// No reference to the value of the node should be alive, like `Node::replace_value`.
+    unsafe fn apply_shared<K, R, I>(node: &Node<K, V, R, I>, delta: &V) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        let value = node.value().clone() + delta.clone();
+        node.replace_value(value);
+
+        let pending = match node.aug.0.take() {
+            Some(pending) => pending + delta.clone(),
+            None => delta.clone(),
+        };
+        node.aug.0.set(Some(pending));
+    }
+}
+
+impl<V> Default for Shift<V> {
+    fn default() -> Self {
+        Shift(Cell::new(None))
+    }
+}
+
+impl<V: Clone> Clone for Shift<V> {
+    fn clone(&self) -> Self {
+        let pending = self.0.take();
+        self.0.set(pending.clone());
+        Shift(Cell::new(pending))
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Shift<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pending = self.0.take();
+        let result = f.debug_tuple("Shift").field(&pending).finish();
+        self.0.set(pending);
+        result
+    }
+}
+
+impl<K, V> Augment<K, V> for Shift<V> where V: Clone + Add<Output=V> {
+    fn push_down<R, I>(node: &mut Node<K, V, R, I>) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        Shift::push_down_shared(node);
+    }
+
+    fn push_down_shared<R, I>(node: &Node<K, V, R, I>) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        let delta = match node.aug.0.take() {
+            Some(delta) => delta,
+            None => return,
+        };
+
+        // Reads push the node down before reaching its children, so nothing borrows them
+        for child in node.left.iter().chain(node.right.iter()) {
+            unsafe { Shift::apply_shared(child, &delta) }
+        }
+    }
+}
+
+impl<K, V> Lazy<K, V> for Shift<V> where V: Clone + Add<Output=V> {
+    type Tag = V;
+
+    fn apply<R, I>(node: &mut Node<K, V, R, I>, delta: &V) where
+        R: Rule, I: Indirect<K, V, R, Aug=Self>
+    {
+        unsafe { Shift::apply_shared(node, delta) }
+    }
+}
@@ -0,0 +1,490 @@
+//! Binary snapshot of a tree, which keeps its shape.
+//!
+//! All integers are little endian. A snapshot of version 1 consists of:
+//!
+//! | Offset | Size | Content                                       |
+//! |--------|------|-----------------------------------------------|
+//! | 0      | 8    | Magic bytes `SPTRMAP\0`                        |
+//! | 8      | 4    | Version, which is 1                           |
+//! | 12     | 4    | Size of a key, `Pod::SIZE` of the key type     |
+//! | 16     | 4    | Size of a value, `Pod::SIZE` of the value type |
+//! | 20     | 4    | Reserved, which is 0                          |
+//! | 24     | 8    | Number of nodes `n`                           |
+//! | 32     | `n` records | Nodes in post-order                    |
+//! | ...    | 8    | Checksum of all bytes above                   |
+//!
+//! Each record holds the index of its left child, the index of its right child,
+//! the key and the value in order. Indices are 8 bytes each, with `u64::MAX` for no child.
+//! As records are in post-order, children come before their parent and the root comes last.
+//! Loading allocates nodes in the order of records, so a tree is built in a single pass
+//! and the record `i` takes the slot `i` of a fresh `VecArena`.
+//!
+//! Records are not in the slot order of the arena the map is written from.
+//! Not every arena has slots, like `BoxArena`, and slots of a used arena are in no useful order,
+//! so their parents could come before children and need fixing up after all records are read.
+//! Writing in post-order takes a stack as deep as the tree instead.
+//!
+//! Regulators of rules are not stored, but initialized by `Rule::build` keeping the shape.
+//! Sizes and augmented data are recomputed. So loading costs a `Rule::build` per node,
+//! which does nothing for `Noop`, and sifts down priorities for `RevTreap` like building a heap,
+//! in `O(n)` total for a balanced tree. No rotation is made.
+//!
+//! All records are read into memory and checked against the checksum before any node is made,
+//! so a corrupt snapshot is rejected without touching the arena. It takes a buffer
+//! as large as the records while loading, besides the nodes.
+//!
+//! The checksum is computed over little endian 8-byte words of the data,
+//! where the last word is padded with zeros. Starting from `0xcbf29ce484222325`,
+//! each word is mixed by `h = (h ^ word) * 0x100000001b3` wrapping, then `h ^= h >> 32`.
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::marker::PhantomData;
+use std::{fmt, io};
+
+use tree::{Node, Indirect};
+use tree::rule::Rule;
+
+use super::node::{Edge, adopt_children};
+
+const MAGIC: &[u8; 8] = b"SPTRMAP\0";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const NIL: u64 = u64::MAX;
+/// Number of bytes buffered at once while writing or reading records.
+const BLOCK_SIZE: usize = 1 << 16;
+
+/// Value with a fixed-size binary encoding, which can be stored in snapshots.
+pub trait Pod: Sized {
+    /// Number of bytes of the encoding.
+    const SIZE: usize;
+
+    /// Write the value into `buf`, which is `SIZE` bytes long.
+    fn encode(&self, buf: &mut [u8]);
+
+    /// Read the value from `buf`, or `None` if the bytes are not a valid value.
+    fn decode(buf: &[u8]) -> Option<Self>;
+}
+
+macro_rules! pod_number {
+    ($($T:ty)*) => ($(
+        impl Pod for $T {
+            const SIZE: usize = ::std::mem::size_of::<$T>();
+
+            fn encode(&self, buf: &mut [u8]) {
+                buf.copy_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(buf: &[u8]) -> Option<Self> {
+                Some(<$T>::from_le_bytes(buf.try_into().ok()?))
+            }
+        }
+    )*);
+}
+
+pod_number!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 f32 f64);
+
+/// Encoded as `u64` to be portable between platforms.
+impl Pod for usize {
+    const SIZE: usize = 8;
+
+    fn encode(&self, buf: &mut [u8]) {
+        (*self as u64).encode(buf);
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        u64::decode(buf)?.try_into().ok()
+    }
+}
+
+/// Encoded as `i64` to be portable between platforms.
+impl Pod for isize {
+    const SIZE: usize = 8;
+
+    fn encode(&self, buf: &mut [u8]) {
+        (*self as i64).encode(buf);
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        i64::decode(buf)?.try_into().ok()
+    }
+}
+
+impl Pod for bool {
+    const SIZE: usize = 1;
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf[0] = *self as u8;
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        match buf[0] {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Pod for char {
+    const SIZE: usize = 4;
+
+    fn encode(&self, buf: &mut [u8]) {
+        (*self as u32).encode(buf);
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        ::std::char::from_u32(u32::decode(buf)?)
+    }
+}
+
+impl Pod for () {
+    const SIZE: usize = 0;
+
+    fn encode(&self, _buf: &mut [u8]) {}
+
+    fn decode(_buf: &[u8]) -> Option<Self> {
+        Some(())
+    }
+}
+
+impl<T: Pod, const N: usize> Pod for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+
+    fn encode(&self, buf: &mut [u8]) {
+        for (item, buf) in self.iter().zip(buf.chunks_exact_mut(T::SIZE.max(1))) {
+            item.encode(buf);
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let mut items = Vec::with_capacity(N);
+
+        for i in 0..N {
+            items.push(T::decode(&buf[i * T::SIZE..(i + 1) * T::SIZE])?);
+        }
+
+        items.try_into().ok()
+    }
+}
+
+impl<A: Pod, B: Pod> Pod for (A, B) {
+    const SIZE: usize = A::SIZE + B::SIZE;
+
+    fn encode(&self, buf: &mut [u8]) {
+        let (a, b) = buf.split_at_mut(A::SIZE);
+        self.0.encode(a);
+        self.1.encode(b);
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let (a, b) = buf.split_at(A::SIZE);
+        Some((A::decode(a)?, B::decode(b)?))
+    }
+}
+
+/// Failure of reading a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Reading from the source failed.
+    Io(io::Error),
+    /// The source ended before the snapshot does.
+    Truncated,
+    /// The source doesn't start with the magic bytes.
+    Magic,
+    /// The snapshot is of a version which can't be read.
+    Version(u32),
+    /// Sizes of keys and values in the snapshot differ from the types to be read.
+    Layout { key: u32, value: u32 },
+    /// The record links its children wrongly, or holds bytes invalid for its key or value.
+    Corrupt { index: u64 },
+    /// The checksum stored in the snapshot differs from the one of its data.
+    Checksum { stored: u64, actual: u64 },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::Io(ref err) =>
+                write!(f, "failed to read snapshot: {}", err),
+            SnapshotError::Truncated =>
+                write!(f, "snapshot is truncated"),
+            SnapshotError::Magic =>
+                write!(f, "not a snapshot"),
+            SnapshotError::Version(version) =>
+                write!(f, "unsupported snapshot version {}", version),
+            SnapshotError::Layout { key, value } =>
+                write!(f, "snapshot has {}-byte keys and {}-byte values", key, value),
+            SnapshotError::Corrupt { index } =>
+                write!(f, "record {} of snapshot is corrupt", index),
+            SnapshotError::Checksum { stored, actual } =>
+                write!(f, "snapshot checksum {:016x} doesn't match {:016x}", stored, actual),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SnapshotError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::Truncated,
+            _ => SnapshotError::Io(err),
+        }
+    }
+}
+
+/// Word-wise FNV-1a, which is described in the module documentation.
+struct Checksum {
+    hash: u64,
+    tail: [u8; 8],
+    tail_len: usize,
+}
+
+impl Checksum {
+    fn new() -> Self {
+        Checksum {
+            hash: 0xcbf2_9ce4_8422_2325,
+            tail: [0; 8],
+            tail_len: 0,
+        }
+    }
+
+    fn mix(&mut self, word: [u8; 8]) {
+        self.hash = (self.hash ^ u64::from_le_bytes(word)).wrapping_mul(0x100_0000_01b3);
+        self.hash ^= self.hash >> 32;
+    }
+
+    fn update(&mut self, mut bytes: &[u8]) {
+        if self.tail_len > 0 {
+            let len = bytes.len().min(8 - self.tail_len);
+            self.tail[self.tail_len..self.tail_len + len].copy_from_slice(&bytes[..len]);
+            self.tail_len += len;
+            bytes = &bytes[len..];
+
+            if self.tail_len < 8 {
+                return;
+            }
+
+            let word = self.tail;
+            self.mix(word);
+            self.tail_len = 0;
+        }
+
+        let mut words = bytes.chunks_exact(8);
+
+        for word in &mut words {
+            self.mix(word.try_into().unwrap());
+        }
+
+        let rest = words.remainder();
+        self.tail[..rest.len()].copy_from_slice(rest);
+        self.tail_len = rest.len();
+    }
+
+    fn finish(mut self) -> u64 {
+        if self.tail_len > 0 {
+            let mut word = [0; 8];
+            word[..self.tail_len].copy_from_slice(&self.tail[..self.tail_len]);
+            self.mix(word);
+        }
+
+        self.hash
+    }
+}
+
+/// Write the tree as a snapshot described in the module documentation.
+pub(crate) fn write_snapshot<K, V, R, I, W>(root: &Option<I>, w: &mut W) -> io::Result<()> where
+    K: Pod,
+    V: Pod,
+    R: Rule,
+    I: Indirect<K, V, R>,
+    W: io::Write,
+{
+    let record_size = 16 + K::SIZE + V::SIZE;
+    let mut checksum = Checksum::new();
+    let mut buf = Vec::with_capacity(BLOCK_SIZE + record_size);
+
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&(K::SIZE as u32).to_le_bytes());
+    buf.extend_from_slice(&(V::SIZE as u32).to_le_bytes());
+    buf.extend_from_slice(&0u32.to_le_bytes());
+    buf.extend_from_slice(&(root.len() as u64).to_le_bytes());
+
+    // Nodes with whether their children are written, and indices of subtrees written
+    let mut stack: Vec<(&I, bool)> = root.iter().map(|root| (root, false)).collect();
+    let mut written: Vec<u64> = Vec::new();
+    let mut index = 0;
+
+    while let Some((node, children_written)) = stack.pop() {
+        if !children_written {
+            stack.push((node, true));
+            node.push_down_shared();
+            stack.extend(node.right.iter().map(|right| (right, false)));
+            stack.extend(node.left.iter().map(|left| (left, false)));
+            continue;
+        }
+
+        // The right subtree is written after the left one
+        let right = node.right.as_ref().map_or(NIL, |_| written.pop().unwrap());
+        let left = node.left.as_ref().map_or(NIL, |_| written.pop().unwrap());
+
+        let start = buf.len();
+        buf.resize(start + record_size, 0);
+        let record = &mut buf[start..];
+        record[..8].copy_from_slice(&left.to_le_bytes());
+        record[8..16].copy_from_slice(&right.to_le_bytes());
+        node.key().encode(&mut record[16..16 + K::SIZE]);
+        node.value().encode(&mut record[16 + K::SIZE..]);
+
+        written.push(index);
+        index += 1;
+
+        if buf.len() >= BLOCK_SIZE {
+            checksum.update(&buf);
+            w.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+
+    checksum.update(&buf);
+    buf.extend_from_slice(&checksum.finish().to_le_bytes());
+    w.write_all(&buf)
+}
+
+/// Subtrees read so far, which are dropped without recursion if reading fails.
+struct Subtrees<K, V, R, I>(Vec<Option<I>>, PhantomData<(K, V, R)>) where
+    R: Rule,
+    I: Indirect<K, V, R>;
+
+impl<K, V, R, I> Drop for Subtrees<K, V, R, I> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    fn drop(&mut self) {
+        // A crafted snapshot may chain all records, too deep for recursive drop
+        for subtree in &mut self.0 {
+            subtree.clear();
+        }
+    }
+}
+
+/// Read a snapshot written by `write_snapshot`, putting nodes in boxes made by `alloc`.
+///
+/// All records are read and checked against the checksum before any node is made.
+pub(crate) fn read_snapshot<K, V, R, I, S, F>(mut r: S, mut alloc: F) -> Result<Option<I>, SnapshotError> where
+    K: Pod,
+    V: Pod,
+    R: Rule,
+    I: Indirect<K, V, R>,
+    S: io::Read,
+    F: FnMut(Node<K, V, R, I>) -> I,
+{
+    let mut checksum = Checksum::new();
+    let mut header = [0; HEADER_SIZE];
+
+    r.read_exact(&mut header[..MAGIC.len()])?;
+    if header[..MAGIC.len()] != MAGIC[..] {
+        return Err(SnapshotError::Magic);
+    }
+
+    r.read_exact(&mut header[MAGIC.len()..])?;
+    checksum.update(&header);
+
+    let field = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+
+    if field(8) != VERSION {
+        return Err(SnapshotError::Version(field(8)));
+    }
+
+    if field(12) as usize != K::SIZE || field(16) as usize != V::SIZE {
+        return Err(SnapshotError::Layout { key: field(12), value: field(16) });
+    }
+
+    let len = u64::from_le_bytes(header[24..32].try_into().unwrap());
+    let record_size = 16 + K::SIZE + V::SIZE;
+    // No source holds more bytes than the address space
+    let data_len = TryInto::<usize>::try_into(len).ok()
+        .and_then(|len| len.checked_mul(record_size))
+        .ok_or(SnapshotError::Truncated)?;
+
+    // Grown block by block, so a corrupt length fails on the end of the source, not on allocation
+    let mut data = Vec::with_capacity(data_len.min(BLOCK_SIZE));
+    while data.len() < data_len {
+        let start = data.len();
+        data.resize(start + (data_len - start).min(BLOCK_SIZE), 0);
+        r.read_exact(&mut data[start..])?;
+    }
+    checksum.update(&data);
+
+    let mut stored = [0; 8];
+    r.read_exact(&mut stored)?;
+    let stored = u64::from_le_bytes(stored);
+    let actual = checksum.finish();
+
+    if stored != actual {
+        return Err(SnapshotError::Checksum { stored, actual });
+    }
+
+    // Subtrees built so far, which are taken by their parents
+    let mut subtrees = Subtrees(Vec::with_capacity(len as usize), PhantomData);
+    let mut detached = 0;
+
+    for (index, record) in (0..).zip(data.chunks_exact(record_size)) {
+        let corrupt = SnapshotError::Corrupt { index };
+        let mut take = |offset: usize| -> Result<Option<I>, SnapshotError> {
+            let child = u64::from_le_bytes(record[offset..offset + 8].try_into().unwrap());
+
+            if child == NIL {
+                return Ok(None);
+            }
+
+            match subtrees.0.get_mut(child as usize) {
+                Some(subtree) if child < index && subtree.is_some() => {
+                    detached -= 1;
+                    Ok(subtree.take())
+                }
+                _ => Err(SnapshotError::Corrupt { index }),
+            }
+        };
+
+        let key = K::decode(&record[16..16 + K::SIZE]);
+        let value = V::decode(&record[16 + K::SIZE..]);
+
+        let mut node = match (key, value) {
+            (Some(key), Some(value)) => Node::new(key, value),
+            _ => return Err(corrupt),
+        };
+        node.left = take(0)?;
+        node.right = match take(8) {
+            Ok(right) => right,
+            Err(err) => {
+                node.left.clear();
+                return Err(err);
+            }
+        };
+
+        let mut node = alloc(node);
+        node.refresh();
+        adopt_children(&mut node);
+        R::build(&mut node);
+
+        subtrees.0.push(Some(node));
+        detached += 1;
+    }
+
+    // Every record except the root should be taken by its parent
+    if detached > 1 {
+        return Err(SnapshotError::Corrupt { index: len - 1 });
+    }
+
+    Ok(subtrees.0.pop().and_then(|root| root))
+}
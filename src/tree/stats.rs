@@ -0,0 +1,73 @@
+use arena::ArenaStats;
+use tree::Indirect;
+use tree::rule::Rule;
+
+/// Shape of a tree, measured by `TreeMap::stats`.
+///
+/// Depth of the root is 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// Number of nodes.
+    pub len: usize,
+    /// Number of levels, which is 0 for an empty tree.
+    pub height: usize,
+    /// Depth of the deepest node.
+    pub max_depth: usize,
+    /// Average depth of all nodes.
+    pub avg_depth: f64,
+    /// Number of leaves at each depth.
+    pub leaf_depths: Vec<usize>,
+    /// Number of nodes with only one child.
+    pub single_child: usize,
+    /// Occupancy of the whole arena, which may be shared with other trees.
+    pub arena: Option<ArenaStats>,
+}
+
+/// Measure the shape of the tree, in `O(n)`.
+pub fn stats<K, V, R, I>(root: &Option<I>, arena: Option<ArenaStats>) -> Stats where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    let mut stats = Stats {
+        len: 0,
+        height: 0,
+        max_depth: 0,
+        avg_depth: 0.0,
+        leaf_depths: Vec::new(),
+        single_child: 0,
+        arena,
+    };
+    let mut total_depth = 0;
+
+    let mut stack: Vec<(&I, usize)> = root.iter().map(|root| (root, 0)).collect();
+
+    while let Some((node, depth)) = stack.pop() {
+        stats.len += 1;
+        total_depth += depth;
+
+        if depth >= stats.height {
+            stats.height = depth + 1;
+        }
+
+        match (&node.left, &node.right) {
+            (&None, &None) => {
+                if stats.leaf_depths.len() <= depth {
+                    stats.leaf_depths.resize(depth + 1, 0);
+                }
+
+                stats.leaf_depths[depth] += 1;
+            }
+            (&Some(_), &None) | (&None, &Some(_)) => stats.single_child += 1,
+            _ => {}
+        }
+
+        stack.extend(node.left.iter().chain(node.right.iter()).map(|child| (child, depth + 1)));
+    }
+
+    if stats.len > 0 {
+        stats.max_depth = stats.height - 1;
+        stats.avg_depth = total_depth as f64 / stats.len as f64;
+    }
+
+    stats
+}
@@ -0,0 +1,117 @@
+use std::cmp::Ordering::{Less, Equal};
+use std::error::Error;
+use std::fmt;
+
+use arena::{Boxed, UnsafeBoxed};
+use tree::{Indirect, Augment, Comparator};
+use tree::rule::Rule;
+
+use super::node::Edge;
+
+/// Broken invariant of a tree, found by validation.
+///
+/// Nodes are identified by their in-order positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeViolation {
+    /// The key of the node is not greater than the key of the previous node.
+    Order { index: usize },
+    /// The `up` link of the node doesn't point its parent.
+    UpLink { index: usize },
+    /// The cached size of the node differs from the size of its subtree.
+    Size { index: usize, cached: usize, actual: usize },
+    /// The augmented data of the node disagrees with its children.
+    Aug { index: usize },
+    /// The node breaks the invariant of the rule.
+    Rule { index: usize, reason: &'static str },
+}
+
+impl fmt::Display for TreeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TreeViolation::Order { index } =>
+                write!(f, "node {} is out of order", index),
+            TreeViolation::UpLink { index } =>
+                write!(f, "node {} has a wrong parent link", index),
+            TreeViolation::Size { index, cached, actual } =>
+                write!(f, "node {} caches size {}, but has {} nodes", index, cached, actual),
+            TreeViolation::Aug { index } =>
+                write!(f, "node {} has stale augmented data", index),
+            TreeViolation::Rule { index, reason } =>
+                write!(f, "node {} breaks the rule: {}", index, reason),
+        }
+    }
+}
+
+impl Error for TreeViolation {}
+
+/// Check the whole tree in order, returning the first violation found.
+///
+/// If `strict`, keys should be strictly increasing, otherwise same keys are allowed.
+pub fn validate<K, V, R, I, C>(root: &Option<I>, cmp: &C, strict: bool) -> Result<(), TreeViolation> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+    C: Comparator<K>,
+{
+    // Nodes waiting for their right subtrees, with their parents
+    let mut stack: Vec<(&I, Option<&I>)> = Vec::new();
+    let mut edge = root;
+    let mut parent = None;
+    let mut prev: Option<&K> = None;
+    let mut index = 0;
+
+    loop {
+        while let Some(ref node) = *edge {
+            stack.push((node, parent));
+            parent = Some(node);
+            edge = &node.left;
+        }
+
+        let (node, up) = match stack.pop() {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+
+        if let Some(prev) = prev {
+            match cmp.compare(prev, node.key()) {
+                Less => {}
+                Equal if !strict => {}
+                _ => return Err(TreeViolation::Order { index }),
+            }
+        }
+
+        check_node(node, up, index)?;
+
+        prev = Some(node.key());
+        index += 1;
+        parent = Some(node);
+        edge = &node.right;
+    }
+}
+
+/// Check the link to the parent and the cached data of the node.
+fn check_node<K, V, R, I>(node: &I, parent: Option<&I>, index: usize) -> Result<(), TreeViolation> where
+    R: Rule,
+    I: Indirect<K, V, R>,
+{
+    // Shared nodes have no single parent to point
+    if !Boxed::is_shared(node) {
+        // The link may be broken, so it is compared without dereferencing it
+        let up = node.up.as_ref().map(UnsafeBoxed::as_ptr);
+
+        if up != parent.map(|parent| &**parent as *const _) {
+            return Err(TreeViolation::UpLink { index });
+        }
+    }
+
+    let actual = node.left.len() + node.right.len() + 1;
+
+    if node.size() != actual {
+        return Err(TreeViolation::Size { index, cached: node.size(), actual });
+    }
+
+    if !I::Aug::check(node) {
+        return Err(TreeViolation::Aug { index });
+    }
+
+    R::check(node).map_err(|reason| TreeViolation::Rule { index, reason })
+}
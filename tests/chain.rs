@@ -0,0 +1,59 @@
+extern crate spartacus;
+
+use std::thread;
+
+use spartacus::arena::{ArenaFamily, BoxArena};
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{TreeMap, Node, Link};
+use spartacus::tree::rule::Noop;
+
+const LEN: u32 = 5_000;
+
+/// Run on a small stack, which any recursion over the chain would overflow.
+fn with_small_stack<F: FnOnce() + Send + 'static>(f: F) {
+    thread::Builder::new()
+        .stack_size(1 << 18)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+/// Sorted insertions make a chain of `Noop`, which should not overflow the stack.
+fn sorted_chain<A>() where
+    A: ArenaFamily,
+    A::Arena<Node<u32, u32, Noop, Link<u32, u32, Noop, A>>>: Default,
+{
+    let mut map: TreeMap<u32, u32, Noop, A> = TreeMap::new();
+
+    for key in 0..LEN {
+        assert_eq!(map.insert(key, key), None);
+    }
+
+    assert_eq!(map.stats().height, LEN as usize);
+    assert_eq!(map.len(), LEN as usize);
+    assert_eq!(map.get(&(LEN - 1)), Some(&(LEN - 1)));
+    assert_eq!(map.insert(LEN - 1, 0), Some(LEN - 1));
+    map.validate().unwrap();
+
+    // Remove from the bottom, the middle and the top of the chain
+    assert_eq!(map.remove(&(LEN - 1)), Some(0));
+    assert_eq!(map.remove(&(LEN / 2)), Some(LEN / 2));
+    assert_eq!(map.remove(&0), Some(0));
+    assert_eq!(map.remove(&0), None);
+    assert_eq!(map.len(), LEN as usize - 3);
+    map.validate().unwrap();
+
+    assert_eq!(map.iter().rev().count(), LEN as usize - 3);
+    drop(map);
+}
+
+#[test]
+fn sorted_chain_in_box_arena() {
+    with_small_stack(sorted_chain::<BoxArena>);
+}
+
+#[test]
+fn sorted_chain_in_vec_arena() {
+    with_small_stack(sorted_chain::<VecArena>);
+}
@@ -0,0 +1,126 @@
+extern crate spartacus;
+
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+use spartacus::arena::{Arena, Boxed as BoxedExt};
+use spartacus::arena::vec_arena::{VecArena, Boxed};
+
+struct Counted(Rc<Cell<usize>>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+/// Value holding a box of the same arena, which it reads when dropped.
+struct Linked(u32, Option<Boxed<Linked>>);
+
+impl Drop for Linked {
+    fn drop(&mut self) {
+        if let Some(ref next) = self.1 {
+            // Stale once the arena is cleared
+            let _ = catch_unwind(AssertUnwindSafe(|| next.0));
+        }
+    }
+}
+
+#[test]
+fn values_are_dropped_once() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+
+    let boxes: Vec<_> = (0..10).map(|_| arena.alloc(Counted(drops.clone()))).collect();
+    for _ in 0..5 {
+        std::mem::forget(arena.alloc(Counted(drops.clone())));
+    }
+
+    // No reference to the values is alive
+    unsafe { arena.clear() }
+    assert_eq!(drops.get(), 15);
+    assert_eq!(arena.stats().unwrap().occupied, 0);
+
+    drop(boxes);
+    assert_eq!(drops.get(), 15);
+    arena.validate().unwrap();
+}
+
+#[test]
+fn stale_boxes() {
+    let arena = VecArena::new();
+    let mut stale = arena.alloc(String::from("stale"));
+
+    unsafe { arena.clear() }
+
+    assert_eq!(BoxedExt::slot(&stale), None);
+    assert!(catch_unwind(AssertUnwindSafe(|| stale.len())).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| stale.push('!'))).is_err());
+
+    // The fresh box takes the first slot, which the stale one pointed
+    let fresh = arena.alloc(String::from("fresh"));
+    assert_eq!(BoxedExt::slot(&fresh), Some(0));
+
+    drop(stale);
+    assert_eq!(*fresh, "fresh");
+    assert_eq!(arena.stats().map(|stats| stats.occupied), Some(1));
+    arena.validate().unwrap();
+}
+
+#[test]
+fn stale_dyn_boxes() {
+    let arena = VecArena::new();
+    let erased = arena.alloc(7).unsize(|value| value as &mut dyn ToString);
+    assert_eq!(erased.slot(), Some(0));
+
+    unsafe { arena.clear() }
+
+    assert_eq!(erased.slot(), None);
+    assert!(catch_unwind(AssertUnwindSafe(|| erased.to_string())).is_err());
+    drop(erased);
+    arena.validate().unwrap();
+}
+
+#[test]
+fn stale_unbox() {
+    let arena = VecArena::new();
+    let stale = arena.alloc(1);
+
+    unsafe { arena.clear() }
+
+    assert!(catch_unwind(AssertUnwindSafe(|| BoxedExt::unbox(stale))).is_err());
+    arena.validate().unwrap();
+}
+
+#[test]
+fn values_holding_boxes() {
+    let arena = VecArena::new();
+
+    let mut list = None;
+    for value in 0..100 {
+        list = Some(arena.alloc(Linked(value, list)));
+    }
+    std::mem::forget(list);
+
+    unsafe { arena.clear() }
+    assert_eq!(arena.stats().unwrap().occupied, 0);
+
+    // The storage is kept to be reused
+    let reused = arena.alloc(Linked(0, None));
+    assert_eq!(BoxedExt::slot(&reused), Some(0));
+    assert_eq!(arena.stats().unwrap().slots, 1);
+    arena.validate().unwrap();
+}
+
+#[test]
+fn clear_with_uninit() {
+    let arena: VecArena<u32> = VecArena::new();
+    let uninit = arena.alloc_uninit();
+
+    assert!(catch_unwind(AssertUnwindSafe(|| unsafe { arena.clear() })).is_err());
+    drop(uninit);
+
+    unsafe { arena.clear() }
+    arena.validate().unwrap();
+}
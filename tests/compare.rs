@@ -0,0 +1,100 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::arena::BoxArena;
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{TreeMap, Link, Natural, Reverse};
+use spartacus::tree::rule::{Noop, RevTreap};
+
+type Reversed = TreeMap<u32, u32, RevTreap, VecArena, Link<u32, u32, RevTreap, VecArena>, Reverse<Natural>>;
+
+type CaseInsensitive = fn(&&str, &&str) -> Ordering;
+type Names = TreeMap<&'static str, u32, Noop, BoxArena, Link<&'static str, u32, Noop, BoxArena>, CaseInsensitive>;
+
+fn case_insensitive(a: &&str, b: &&str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+#[test]
+fn reversed_order() {
+    let mut rng = XorShiftRng::from_seed([9, 8, 7, 6]);
+    let mut map = Reversed::new();
+    let mut model = BTreeMap::new();
+
+    for _ in 0..2000 {
+        let key = rng.gen_range(0, 300);
+
+        if rng.gen_range(0, 3) == 0 {
+            assert_eq!(map.remove(&key), model.remove(&key));
+        } else {
+            assert_eq!(map.insert(key, key * 2), model.insert(key, key * 2));
+        }
+
+        assert_eq!(map.get(&key), model.get(&key));
+
+        // Floor is the next greater key in the reversed order, and ceiling the next less one
+        let floor = model.range(key..).next();
+        let ceiling = model.range(..=key).next_back();
+        assert_eq!(map.floor(&key), floor);
+        assert_eq!(map.ceiling(&key), ceiling);
+    }
+
+    map.validate().unwrap();
+    assert!(map.iter().eq(model.iter().rev()));
+
+    // Range from 200 down to, not including, 100
+    let mut cursor = map.lower_bound(&200);
+    let end = map.lower_bound(&100).key();
+    let mut range = Vec::new();
+    while cursor.key() != end {
+        range.push(*cursor.key().unwrap());
+        cursor.move_next();
+    }
+    let expected: Vec<_> = model.range(101..=200).rev().map(|(&k, _)| k).collect();
+    assert_eq!(range, expected);
+}
+
+#[test]
+fn runtime_comparator() {
+    let mut map = Names::with_comparator(case_insensitive);
+
+    for (index, name) in ["carol", "Alice", "dave", "Bob"].iter().enumerate() {
+        assert_eq!(map.insert(name, index as u32), None);
+    }
+
+    let keys: Vec<_> = map.iter().map(|(&key, _)| key).collect();
+    assert_eq!(keys, vec!["Alice", "Bob", "carol", "dave"]);
+
+    assert_eq!(map.get(&"ALICE"), Some(&1));
+    assert_eq!(map.floor(&"c"), Some((&"Bob", &3)));
+    assert_eq!(map.ceiling(&"C"), Some((&"carol", &0)));
+
+    // Entries from "b" up to, not including, "D"
+    let mut cursor = map.lower_bound(&"b");
+    let end = map.lower_bound(&"D").key();
+    let mut range = Vec::new();
+    while cursor.key() != end {
+        range.push(*cursor.key().unwrap());
+        cursor.move_next();
+    }
+    assert_eq!(range, vec!["Bob", "carol"]);
+    map.validate().unwrap();
+}
+
+#[test]
+fn equal_keys_collapse() {
+    let mut map = Names::with_comparator(case_insensitive);
+
+    assert_eq!(map.insert("Alice", 1), None);
+    // The whole entry is replaced, including the key
+    assert_eq!(map.insert("ALICE", 2), Some(1));
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.first_key_value(), Some((&"ALICE", &2)));
+
+    assert_eq!(map.remove(&"alice"), Some(2));
+    assert!(map.is_empty());
+}
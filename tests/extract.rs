@@ -0,0 +1,98 @@
+extern crate spartacus;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use spartacus::arena::BoxArena;
+use spartacus::tree::TreeMap;
+use spartacus::tree::rule::{Noop, RevTreap};
+
+fn chain(len: u32) -> TreeMap<u32, u32, Noop, BoxArena> {
+    let mut map = TreeMap::new();
+    for key in 0..len {
+        map.insert(key, key);
+    }
+    map
+}
+
+#[test]
+fn shape_is_kept_without_matches() {
+    let mut map = chain(100);
+    assert_eq!(map.stats().height, 100);
+
+    assert_eq!(map.extract_if(|_, _| false).count(), 0);
+
+    // Rebuilding would have balanced the chain
+    assert_eq!(map.stats().height, 100);
+    map.validate().unwrap();
+}
+
+#[test]
+fn entries_are_removed_in_place() {
+    let mut map = chain(100);
+
+    let extracted: Vec<_> = map.extract_if(|key, value| {
+        *value += 1;
+        key % 10 == 0
+    }).collect();
+
+    assert_eq!(extracted, (0..10).map(|key| (key * 10, key * 10 + 1)).collect::<Vec<_>>());
+    assert_eq!(map.len(), 90);
+    assert_eq!(map.stats().height, 90);
+    assert!(map.iter().all(|(key, value)| *value == key + 1));
+    map.validate().unwrap();
+}
+
+#[test]
+fn unvisited_entries_are_kept() {
+    let mut map: TreeMap<u32, u32, RevTreap, BoxArena> = (0..100).map(|key| (key, key)).collect();
+
+    let first: Vec<_> = map.extract_if(|key, _| key % 2 == 1).take(5).collect();
+    assert_eq!(first, vec![(1, 1), (3, 3), (5, 5), (7, 7), (9, 9)]);
+    assert_eq!(map.len(), 95);
+    map.validate().unwrap();
+
+    // Nothing is left to restore when the iterator stops early
+    assert_eq!(map.extract_if(|key, _| *key >= 50).next(), Some((50, 50)));
+
+    assert_eq!(map.len(), 94);
+    assert_eq!(map.get(&51), Some(&51));
+    map.validate().unwrap();
+}
+
+#[test]
+fn retain_rebuilds_balanced() {
+    let mut map = chain(1000);
+
+    map.retain(|key, value| {
+        *value += 1;
+        key % 3 == 0
+    });
+
+    assert_eq!(map.len(), 334);
+    assert!(map.iter().all(|(key, value)| key % 3 == 0 && *value == key + 1));
+    assert!(map.stats().height <= 9);
+    map.validate().unwrap();
+
+    map.retain(|_, _| false);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn retain_keeps_entries_when_panicking() {
+    let mut map: TreeMap<u32, u32, RevTreap, BoxArena> = (0..100).map(|key| (key, key)).collect();
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        map.retain(|&key, _| {
+            assert!(key != 50);
+            key % 2 == 0
+        });
+    }));
+
+    // Odd keys before the panic are removed, along with the key it panics on
+    assert!(result.is_err());
+    assert_eq!(map.len(), 25 + 49);
+    assert_eq!(map.get(&49), None);
+    assert_eq!(map.get(&50), None);
+    assert_eq!(map.get(&51), Some(&51));
+    map.validate().unwrap();
+}
@@ -0,0 +1,127 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::arena::{ArenaFamily, BoxArena};
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{IntervalMap, Interval, MaxEnd, Node, Link};
+use spartacus::tree::rule::{Rule, Noop, RevTreap};
+
+type Model = BTreeMap<(u32, u32), u32>;
+
+fn overlaps(range: &Range<u32>, other: &Range<u32>) -> bool {
+    range.start < range.end && other.start < other.end
+        && range.start < other.end && other.start < range.end
+}
+
+/// Ranges overlapping with `range` by a brute-force scan, ordered by their start.
+fn model_overlapping(model: &Model, range: &Range<u32>) -> Vec<(Range<u32>, u32)> {
+    model.iter()
+        .map(|(&(start, end), &value)| (start..end, value))
+        .filter(|(other, _)| overlaps(range, other))
+        .collect()
+}
+
+fn model_containing(model: &Model, point: u32) -> Vec<(Range<u32>, u32)> {
+    model.iter()
+        .filter(|&(&(start, end), _)| start <= point && point < end)
+        .map(|(&(start, end), &value)| (start..end, value))
+        .collect()
+}
+
+fn random_range(rng: &mut XorShiftRng) -> Range<u32> {
+    let start = rng.gen_range(0, 100);
+    // Mostly short ranges, with some empty or inverted ones
+    match rng.gen_range(0, 10) {
+        0 => start..start,
+        1 => start..start.saturating_sub(rng.gen_range(1, 5)),
+        2 => start..start + rng.gen_range(20, 60),
+        _ => start..start + rng.gen_range(1, 8),
+    }
+}
+
+fn random_operations<R, A>() where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<Interval<u32>, u32, R, Link<Interval<u32>, u32, R, A, MaxEnd<u32>>>>: Default,
+{
+    let mut rng = XorShiftRng::from_seed([2, 4, 6, 8]);
+    let mut map: IntervalMap<u32, u32, R, A> = IntervalMap::new();
+    let mut model = Model::new();
+
+    for _ in 0..3000 {
+        let range = random_range(&mut rng);
+        let key = (range.start, range.end);
+
+        match rng.gen_range(0, 6) {
+            0..=2 => {
+                let value = rng.gen();
+                assert_eq!(map.insert(range, value), model.insert(key, value));
+            }
+            3 => assert_eq!(map.remove(&range), model.remove(&key)),
+            4 => {
+                let found: Vec<_> = map.overlapping(range.clone())
+                    .map(|(range, &value)| (range.clone(), value))
+                    .collect();
+                let expected = model_overlapping(&model, &range);
+                assert_eq!(map.any_overlap(range), !expected.is_empty());
+                assert_eq!(found, expected);
+            }
+            _ => {
+                let point = range.start;
+                let found: Vec<_> = map.containing(point)
+                    .map(|(range, &value)| (range.clone(), value))
+                    .collect();
+                assert_eq!(found, model_containing(&model, point));
+            }
+        }
+
+        map.validate().unwrap();
+        assert_eq!(map.len(), model.len());
+    }
+}
+
+#[test]
+fn queries_follow_model() {
+    random_operations::<RevTreap, BoxArena>();
+    random_operations::<RevTreap, VecArena>();
+    random_operations::<Noop, BoxArena>();
+}
+
+#[test]
+fn empty_ranges() {
+    let mut map: IntervalMap<u32, &str, RevTreap, BoxArena> = IntervalMap::new();
+    map.insert(3..7, "a");
+    map.insert(10..10, "empty");
+    map.insert(Range { start: 12, end: 11 }, "inverted");
+
+    assert_eq!(map.overlapping(5..5).count(), 0);
+    assert!(!map.any_overlap(5..5));
+    assert!(!map.any_overlap(Range { start: 6, end: 4 }));
+    assert!(map.any_overlap(6..8));
+
+    // Stored empty ranges contain no point either
+    assert_eq!(map.overlapping(9..13).count(), 0);
+    assert_eq!(map.containing(10).count(), 0);
+    assert_eq!(map.get(&(10..10)), Some(&"empty"));
+
+    // Only empty ranges are left, so queries skip them all
+    map.remove(&(3..7));
+    map.validate().unwrap();
+    assert!(!map.any_overlap(0..100));
+}
+
+#[test]
+fn half_open_bounds() {
+    let mut map: IntervalMap<u32, u32, RevTreap, BoxArena> = IntervalMap::new();
+    map.insert(3..7, 0);
+
+    assert!(!map.any_overlap(0..3));
+    assert!(!map.any_overlap(7..9));
+    assert!(map.any_overlap(6..9));
+    assert_eq!(map.containing(3).count(), 1);
+    assert_eq!(map.containing(7).count(), 0);
+}
@@ -0,0 +1,108 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::arena::BoxArena;
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{TreeMap, Link, Shift};
+use spartacus::tree::rule::{Noop, RevTreap};
+
+type ShiftMap<R, A> = TreeMap<u32, i64, R, A, Link<u32, i64, R, A, Shift<i64>>>;
+
+fn rng() -> XorShiftRng {
+    XorShiftRng::from_seed([7, 11, 13, 17])
+}
+
+/// Every read through `&self` should see the tags applied by `update_range`.
+fn check_reads<R, A>(map: &ShiftMap<R, A>, model: &BTreeMap<u32, i64>) where
+    R: spartacus::tree::rule::Rule,
+    A: spartacus::arena::ArenaFamily,
+{
+    for (key, value) in model {
+        assert_eq!(map.get(key), Some(value));
+        assert_eq!(map[key], *value);
+    }
+
+    assert!(map.iter().map(|(k, v)| (*k, *v)).eq(model.iter().map(|(k, v)| (*k, *v))));
+    assert!(map.iter().rev().map(|(k, v)| (*k, *v)).eq(model.iter().rev().map(|(k, v)| (*k, *v))));
+    assert_eq!(format!("{:?}", map), format!("{:?}", model));
+
+    for key in 0..100 {
+        assert_eq!(map.floor(&key), model.range(..=key).next_back());
+        assert_eq!(map.ceiling(&key), model.range(key..).next());
+
+        let mut cursor = map.lower_bound(&key);
+        let mut expected = model.range(key..);
+        // Stop at the ghost position, past which the cursor wraps around
+        for _ in 0..3 {
+            let entry = expected.next();
+            assert_eq!(cursor.key().zip(cursor.value()), entry);
+            if entry.is_none() {
+                break;
+            }
+            cursor.move_next();
+        }
+
+        let mut cursor = map.lower_bound(&key);
+        cursor.move_prev();
+        assert_eq!(cursor.key().zip(cursor.value()), model.range(..key).next_back());
+    }
+}
+
+fn random_updates<R, A>() where
+    R: spartacus::tree::rule::Rule,
+    A: spartacus::arena::ArenaFamily,
+    A::Arena<spartacus::tree::Node<u32, i64, R, Link<u32, i64, R, A, Shift<i64>>>>: Default,
+{
+    let mut rng = rng();
+    let mut map: ShiftMap<R, A> = TreeMap::new();
+    let mut model = BTreeMap::new();
+
+    for _ in 0..300 {
+        match rng.gen_range(0, 4) {
+            0 => {
+                let key = rng.gen_range(0, 100);
+                let value = rng.gen_range(-1000, 1000);
+                assert_eq!(map.insert(key, value), model.insert(key, value));
+            }
+            1 => {
+                let key = rng.gen_range(0, 100);
+                assert_eq!(map.remove(&key), model.remove(&key));
+            }
+            _ => {
+                let start = rng.gen_range(0, 100);
+                let end = rng.gen_range(start, 101);
+                let delta = rng.gen_range(-10, 10);
+                map.update_range(start..end, delta);
+
+                for (_, value) in model.range_mut(start..end) {
+                    *value += delta;
+                }
+
+                check_reads(&map, &model);
+            }
+        }
+    }
+
+    map.validate().unwrap();
+}
+
+#[test]
+fn reads_see_pending_tags() {
+    random_updates::<RevTreap, VecArena>();
+    random_updates::<RevTreap, BoxArena>();
+    random_updates::<Noop, BoxArena>();
+}
+
+#[test]
+fn derived_traits_see_pending_tags() {
+    let mut map: ShiftMap<RevTreap, BoxArena> = (0..80).map(|key| (key, 0)).collect();
+    map.update_range(10..70, 5);
+
+    let expected: ShiftMap<RevTreap, BoxArena> =
+        (0..80).map(|key| (key, if (10..70).contains(&key) { 5 } else { 0 })).collect();
+    assert!(map == expected);
+    assert_eq!(map.clone().iter().filter(|&(_, v)| *v == 5).count(), 60);
+}
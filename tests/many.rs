@@ -0,0 +1,106 @@
+extern crate spartacus;
+
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+use spartacus::arena::{Arena, ArenaStats, Boxed as BoxedExt};
+use spartacus::arena::vec_arena::VecArena;
+
+#[derive(Debug)]
+struct Counted(Rc<Cell<usize>>, u32);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+fn stats<T>(arena: &VecArena<T>) -> (usize, usize, usize) {
+    let ArenaStats { slots, occupied, free } = arena.stats().unwrap();
+    (slots, occupied, free)
+}
+
+fn slots<T>(boxes: &[spartacus::arena::vec_arena::Boxed<T>]) -> Vec<usize> {
+    boxes.iter().map(|boxed| BoxedExt::slot(boxed).unwrap()).collect()
+}
+
+#[test]
+fn contiguous_in_fresh_arena() {
+    let arena = VecArena::new();
+    let boxes = arena.alloc_many(0..100);
+
+    assert_eq!(slots(&boxes), (0..100).collect::<Vec<_>>());
+    assert!(boxes.iter().map(|boxed| **boxed).eq(0..100));
+    arena.validate().unwrap();
+}
+
+#[test]
+fn holes_are_filled_first() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+
+    let mut boxes: Vec<_> = (0..10).map(|value| Some(arena.alloc(Counted(drops.clone(), value)))).collect();
+    for index in &[2, 5, 7] {
+        boxes[*index] = None;
+    }
+    assert_eq!(stats(&arena), (10, 7, 3));
+
+    // The last freed slot is reused first, then the rest are appended
+    let many = arena.alloc_many((10..15).map(|value| Counted(drops.clone(), value)));
+    assert_eq!(slots(&many), vec![7, 5, 2, 10, 11]);
+    assert_eq!(many.iter().map(|boxed| boxed.1).collect::<Vec<_>>(), vec![10, 11, 12, 13, 14]);
+    assert_eq!(stats(&arena), (12, 12, 0));
+    arena.validate().unwrap();
+
+    // Every value is dropped exactly once
+    drops.set(0);
+    arena.free_many(many.into_iter().chain(boxes.into_iter().flatten()));
+    assert_eq!(drops.get(), 12);
+    assert_eq!(stats(&arena), (12, 0, 12));
+    arena.validate().unwrap();
+}
+
+#[test]
+fn free_many_with_other_boxes() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+    let other = VecArena::new();
+
+    let mut boxes = arena.alloc_many((0..5).map(|value| Counted(drops.clone(), value)));
+    boxes.extend(other.alloc_many((0..3).map(|value| Counted(drops.clone(), value))));
+    let stale = arena.alloc(Counted(drops.clone(), 5));
+
+    // Values of the stale box are dropped by `clear`, and its handle is ignored later
+    let kept = other.alloc(Counted(drops.clone(), 3));
+    unsafe { arena.clear() }
+    assert_eq!(drops.get(), 6);
+    boxes.push(stale);
+
+    // Boxes of the other arena are dropped as usual
+    arena.free_many(boxes);
+    assert_eq!(drops.get(), 9);
+    assert_eq!(stats(&other), (4, 1, 3));
+    assert_eq!(kept.1, 3);
+    arena.validate().unwrap();
+    other.validate().unwrap();
+}
+
+#[test]
+fn alloc_many_panic() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_many((0..10).map(|value| {
+            assert!(value < 5, "no value");
+            Counted(drops.clone(), value)
+        }))
+    }));
+
+    // Boxes allocated before the panic are freed
+    assert!(result.is_err());
+    assert_eq!(drops.get(), 5);
+    assert_eq!(stats(&arena), (5, 0, 5));
+    arena.validate().unwrap();
+}
@@ -0,0 +1,75 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::arena::BoxArena;
+use spartacus::tree::TreeMap;
+use spartacus::tree::rule::{Noop, Rule, RevTreap};
+
+fn model_union(a: &BTreeMap<u32, u32>, b: &BTreeMap<u32, u32>) -> BTreeMap<u32, u32> {
+    let mut union = a.clone();
+    for (&key, &value) in b {
+        *union.entry(key).or_insert(0) += value;
+    }
+    union
+}
+
+fn check<R: Rule>(a: BTreeMap<u32, u32>, b: BTreeMap<u32, u32>) {
+    // Inserted in order, unlike `collect` which builds balanced trees
+    let map = |model: &BTreeMap<u32, u32>| {
+        let mut map: TreeMap<u32, u32, R, BoxArena> = TreeMap::new();
+        for (&key, &value) in model {
+            map.insert(key, value);
+        }
+        map
+    };
+
+    let mut union = map(&a);
+    union.union_with(map(&b), |_, value, other| *value += other);
+    union.validate().unwrap();
+    assert!(union.iter().eq(model_union(&a, &b).iter()));
+
+    let mut intersection = map(&a);
+    intersection.intersection_with(map(&b), |_, value, other| *value = *value * 2 + other);
+    intersection.validate().unwrap();
+    let expected: BTreeMap<_, _> = a.iter()
+        .filter_map(|(&key, &value)| b.get(&key).map(|&other| (key, value * 2 + other)))
+        .collect();
+    assert!(intersection.iter().eq(expected.iter()));
+
+    let mut difference = map(&a);
+    difference.difference(map(&b));
+    difference.validate().unwrap();
+    let expected: BTreeMap<_, _> = a.iter()
+        .filter(|&(key, _)| !b.contains_key(key))
+        .map(|(&k, &v)| (k, v))
+        .collect();
+    assert!(difference.iter().eq(expected.iter()));
+}
+
+#[test]
+fn random_merges() {
+    let mut rng = XorShiftRng::from_seed([3, 5, 7, 9]);
+
+    for _ in 0..50 {
+        let mut random = |len| -> BTreeMap<u32, u32> {
+            (0..len).map(|_| (rng.gen_range(0, 300), rng.gen_range(0, 1000))).collect()
+        };
+        let a = random(200);
+        let b = random(100);
+
+        check::<RevTreap>(a.clone(), b.clone());
+        check::<Noop>(a, b);
+    }
+}
+
+#[test]
+fn deep_chains() {
+    // Sorted insertions make a chain of `Noop`, as deep as it is long
+    let a: BTreeMap<u32, u32> = (0..5_000).map(|key| (key * 2, key)).collect();
+    let b: BTreeMap<u32, u32> = (0..5_000).map(|key| (key * 3, key)).collect();
+
+    check::<Noop>(a, b);
+}
@@ -0,0 +1,86 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::tree::PersistentMap;
+use spartacus::tree::rule::{Rule, Noop, RevTreap};
+
+fn check<R: Rule + Clone>(map: &PersistentMap<u32, u32, R>, model: &BTreeMap<u32, u32>) {
+    assert_eq!(map.len(), model.len());
+    for key in 0..100 {
+        assert_eq!(map.get(&key), model.get(&key));
+    }
+}
+
+/// Every version should stay as it was, while later ones are modified.
+fn versions<R: Rule + Clone>() {
+    let mut rng = XorShiftRng::from_seed([4, 3, 2, 1]);
+    let mut versions = vec![(PersistentMap::<u32, u32, R>::new(), BTreeMap::new())];
+
+    for step in 0..500 {
+        // Branch off a random earlier version
+        let (map, model) = {
+            let (ref map, ref model) = versions[rng.gen_range(0, versions.len())];
+            let key = rng.gen_range(0, 100);
+
+            if rng.gen_range(0, 3) == 0 {
+                let mut model = model.clone();
+                model.remove(&key);
+                (map.remove(&key), model)
+            } else {
+                let mut model = model.clone();
+                model.insert(key, step);
+                (map.insert(key, step), model)
+            }
+        };
+
+        check(&map, &model);
+        versions.push((map, model));
+
+        // Drop intermediate versions, which others may share nodes with
+        if versions.len() > 30 {
+            let index = rng.gen_range(1, versions.len() - 1);
+            versions.remove(index);
+        }
+
+        if step % 50 == 0 {
+            for (map, model) in &versions {
+                check(map, model);
+            }
+        }
+    }
+
+    for (map, model) in &versions {
+        check(map, model);
+    }
+}
+
+#[test]
+fn old_versions_are_kept() {
+    versions::<Noop>();
+    versions::<RevTreap>();
+}
+
+#[test]
+fn chain_of_versions() {
+    let empty: PersistentMap<u32, u32, RevTreap> = PersistentMap::new();
+    let one = empty.insert(1, 10);
+    let two = one.insert(2, 20);
+    let replaced = two.insert(1, 11);
+    let removed = replaced.remove(&2);
+
+    assert!(empty.is_empty());
+    assert_eq!((one.len(), one.get(&1), one.get(&2)), (1, Some(&10), None));
+    assert_eq!((two.len(), two.get(&1), two.get(&2)), (2, Some(&10), Some(&20)));
+    assert_eq!((replaced.len(), replaced.get(&1)), (2, Some(&11)));
+    assert_eq!((removed.len(), removed.get(&1), removed.get(&2)), (1, Some(&11), None));
+
+    // Later versions don't depend on the earlier ones staying alive
+    drop((empty, one, two));
+    assert_eq!(replaced.get(&2), Some(&20));
+    let again = removed.insert(3, 30).remove(&1);
+    assert_eq!((again.len(), again.get(&3)), (1, Some(&30)));
+    assert_eq!(removed.get(&1), Some(&11));
+}
@@ -0,0 +1,141 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::arena::{Arena, ArenaStats, Boxed as BoxedExt};
+use spartacus::arena::ref_arena::{RefArena, ArenaRef, Boxed};
+use spartacus::tree::TreeMap;
+use spartacus::tree::rule::{Noop, RevTreap};
+
+struct Counted<'a>(&'a Cell<usize>);
+
+impl<'a> Drop for Counted<'a> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+/// List whose tail is a box in the same arena.
+struct Cons<'a>(u32, Option<Boxed<'a, Cons<'a>>>);
+
+fn stats<T>(arena: &ArenaRef<'_, T>) -> (usize, usize, usize) {
+    let ArenaStats { slots, occupied, free } = arena.stats().unwrap();
+    (slots, occupied, free)
+}
+
+#[test]
+fn slots_are_reused() {
+    let drops = Cell::new(0);
+    let arena = RefArena::new();
+    let handle = arena.handle();
+
+    let boxes: Vec<_> = (0..3).map(|_| handle.alloc(Counted(&drops))).collect();
+    assert_eq!(stats(&handle), (3, 3, 0));
+
+    let mut boxes = boxes.into_iter();
+    drop(boxes.next());
+    let value = BoxedExt::unbox(boxes.next().unwrap());
+    assert_eq!(drops.get(), 1);
+    drop(value);
+    assert_eq!(drops.get(), 2);
+    assert_eq!(stats(&handle), (3, 1, 2));
+
+    let reused = handle.alloc(Counted(&drops));
+    assert_eq!(stats(&handle), (3, 2, 1));
+
+    drop(reused);
+    drop(boxes);
+    assert_eq!(drops.get(), 4);
+    assert_eq!(stats(&handle), (3, 0, 3));
+    assert!(handle.shares_with(&arena.handle()));
+    assert!(!handle.shares_with(&RefArena::new().handle()));
+}
+
+#[test]
+fn boxes_hold_boxes_of_the_same_arena() {
+    let arena = RefArena::new();
+    let handle = arena.handle();
+
+    let mut list = None;
+    for value in 0..100 {
+        list = Some(handle.alloc(Cons(value, list)));
+    }
+
+    let mut sum = 0;
+    let mut node = list.as_ref();
+    while let Some(cons) = node {
+        sum += cons.0;
+        node = cons.1.as_ref();
+    }
+    assert_eq!(sum, 4950);
+
+    // Taking the tail out drops only the head
+    let tail = list.unwrap().1.take();
+    assert_eq!(stats(&handle), (100, 99, 1));
+    drop(tail);
+    assert_eq!(stats(&handle), (100, 0, 100));
+}
+
+fn random_map<R: spartacus::tree::rule::Rule>() {
+    let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+    let arena = RefArena::new();
+    let mut map: TreeMap<u32, u32, R, ArenaRef> = TreeMap::new_in(arena.handle());
+    let mut model = BTreeMap::new();
+
+    for _ in 0..3000 {
+        let key = rng.gen_range(0, 300);
+
+        if rng.gen() {
+            assert_eq!(map.insert(key, key + 1), model.insert(key, key + 1));
+        } else {
+            assert_eq!(map.remove(&key), model.remove(&key));
+        }
+    }
+
+    map.validate().unwrap();
+    assert!(map.iter().eq(model.iter()));
+
+    let ArenaStats { slots, occupied, free } = map.arena().stats().unwrap();
+    assert_eq!(occupied, model.len());
+    assert_eq!(slots, occupied + free);
+
+    drop(map);
+    assert_eq!(stats(&arena.handle()).1, 0);
+}
+
+#[test]
+fn maps_in_arena() {
+    random_map::<RevTreap>();
+    random_map::<Noop>();
+}
+
+#[test]
+fn maps_sharing_an_arena() {
+    let arena = RefArena::new();
+    let other_arena = RefArena::new();
+
+    let mut map: TreeMap<u32, u32, RevTreap, ArenaRef> = TreeMap::new_in(arena.handle());
+    let mut shared: TreeMap<u32, u32, RevTreap, ArenaRef> = TreeMap::new_in(arena.handle());
+    let mut other: TreeMap<u32, u32, RevTreap, ArenaRef> = TreeMap::new_in(other_arena.handle());
+
+    for key in 0..100 {
+        map.insert(key * 2, 0);
+        shared.insert(key * 2 + 1, 1);
+        other.insert(key * 3, 2);
+    }
+
+    // Nodes of the same arena are taken as they are, others are moved
+    map.union_with(shared, |_, _, _| unreachable!());
+    assert_eq!(stats(&arena.handle()), (200, 200, 0));
+
+    map.union_with(other, |_, value, other| *value += other);
+    assert_eq!(stats(&other_arena.handle()), (100, 0, 100));
+
+    map.validate().unwrap();
+    assert_eq!(map.len(), 200 + 33);
+    assert_eq!(map.get(&6), Some(&2));
+    assert_eq!(map.get(&9), Some(&3));
+}
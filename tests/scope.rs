@@ -0,0 +1,87 @@
+extern crate spartacus;
+
+use std::cell::Cell;
+use std::mem;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+use spartacus::arena::vec_arena::{VecArena, ScopedBoxed};
+
+struct Counted(Rc<Cell<usize>>);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[test]
+fn boxes_are_freed_in_scope() {
+    let sum = VecArena::scope(|scope| {
+        let boxes: Vec<_> = (0..10u64).map(|value| scope.alloc(value)).collect();
+        assert_eq!(scope.stats().occupied, 10);
+
+        let sum = boxes.iter().map(|boxed| **boxed).sum::<u64>();
+        drop(boxes);
+        assert_eq!(scope.stats().occupied, 0);
+
+        // Freed slots are reused
+        let mut boxed = scope.alloc_with(|| 1);
+        *boxed += 1;
+        assert_eq!(ScopedBoxed::unbox(boxed), 2);
+        assert_eq!(scope.stats().slots, 10);
+
+        sum
+    });
+
+    assert_eq!(sum, 45);
+}
+
+#[test]
+fn forgotten_boxes_are_dropped_after_scope() {
+    let drops = Rc::new(Cell::new(0));
+
+    VecArena::scope(|scope| {
+        scope.reserve(100);
+        for _ in 0..100 {
+            mem::forget(scope.alloc(Counted(drops.clone())));
+        }
+
+        drop(scope.alloc(Counted(drops.clone())));
+        assert_eq!(drops.get(), 1);
+    });
+
+    assert_eq!(drops.get(), 101);
+}
+
+#[test]
+fn forgotten_boxes_are_dropped_after_panic() {
+    let drops = Rc::new(Cell::new(0));
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        VecArena::scope(|scope| {
+            mem::forget(scope.alloc(Counted(drops.clone())));
+            let _kept = scope.alloc(Counted(drops.clone()));
+            panic!("scope panics");
+        })
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn nested_scopes() {
+    let (outer, inner) = VecArena::scope(|outer| {
+        let a = outer.alloc(1);
+
+        let inner = VecArena::scope(|inner| {
+            let b = inner.alloc(*a + 1);
+            *b
+        });
+
+        (*a, inner)
+    });
+
+    assert_eq!((outer, inner), (1, 2));
+}
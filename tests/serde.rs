@@ -0,0 +1,101 @@
+#![cfg(feature = "serde")]
+
+extern crate serde_json;
+extern crate spartacus;
+
+use spartacus::arena::{Arena, BoxArena, Boxed as BoxedExt};
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{TreeMap, Link, Reverse, Natural};
+use spartacus::tree::rule::{Noop, RevTreap};
+
+type Reversed = TreeMap<u32, u32, RevTreap, BoxArena, Link<u32, u32, RevTreap, BoxArena>, Reverse<Natural>>;
+
+#[test]
+fn map_round_trip() {
+    let mut map: TreeMap<u32, String, Noop, BoxArena> = TreeMap::new();
+    for key in 0..100 {
+        map.insert(key, key.to_string());
+    }
+    assert_eq!(map.stats().height, 100);
+
+    let json = serde_json::to_string(&map).unwrap();
+    assert!(json.starts_with(r#"{"0":"0","1":"1","2":"2","#));
+
+    // Built in bulk, so the chain comes back balanced
+    let restored: TreeMap<u32, String, Noop, BoxArena> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, map);
+    assert_eq!(restored.stats().height, 7);
+    restored.validate().unwrap();
+}
+
+#[test]
+fn map_in_any_order() {
+    let json = r#"{"3": 30, "1": 10, "2": 20, "1": 11}"#;
+
+    let map: TreeMap<u32, u32, RevTreap, VecArena> = serde_json::from_str(json).unwrap();
+    assert_eq!(map.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>(), vec![(1, 11), (2, 20), (3, 30)]);
+    map.validate().unwrap();
+
+    // Serialized in the order of the comparator
+    let reversed: Reversed = serde_json::from_str(json).unwrap();
+    assert_eq!(serde_json::to_string(&reversed).unwrap(), r#"{"3":30,"2":20,"1":11}"#);
+    reversed.validate().unwrap();
+}
+
+#[test]
+fn arena_restore_and_claim() {
+    let arena = VecArena::new();
+    let boxes: Vec<_> = (0..6).map(|index| arena.alloc(format!("value {}", index))).collect();
+
+    // Slots 1 and 4 are freed, so the restored arena has holes there
+    let kept: Vec<_> = boxes.into_iter().enumerate()
+        .filter_map(|(index, boxed)| if index % 3 == 1 { None } else { Some(boxed) })
+        .collect();
+    let indices: Vec<_> = kept.iter().map(|boxed| BoxedExt::slot(boxed).unwrap()).collect();
+    assert_eq!(indices, vec![0, 2, 3, 5]);
+
+    let json = serde_json::to_string(&arena).unwrap();
+    let restored: VecArena<String> = serde_json::from_str(&json).unwrap();
+    restored.validate().unwrap();
+    assert_eq!(restored.stats().map(|stats| (stats.slots, stats.occupied, stats.free)), Some((6, 4, 2)));
+
+    let claimed: Vec<_> = indices.iter().map(|&index| restored.claim(index).unwrap()).collect();
+    for (boxed, original) in claimed.iter().zip(&kept) {
+        assert_eq!(**boxed, **original);
+    }
+
+    // Each handle is taken once, and empty slots have none
+    assert!(restored.claim(0).is_none());
+    assert!(restored.claim(1).is_none());
+    assert!(restored.claim(6).is_none());
+
+    // Holes are reused in ascending order
+    let new = restored.alloc(String::from("new"));
+    assert_eq!(BoxedExt::slot(&new), Some(1));
+
+    drop(claimed);
+    assert_eq!(restored.stats().map(|stats| stats.occupied), Some(1));
+    restored.validate().unwrap();
+}
+
+#[test]
+fn unclaimed_slots() {
+    let arena = VecArena::new();
+    let boxes: Vec<_> = (0..4).map(|index| arena.alloc(vec![index; 3])).collect();
+    let json = serde_json::to_string(&arena).unwrap();
+    drop(boxes);
+
+    // Values never claimed are dropped with the arena
+    let restored: VecArena<Vec<u32>> = serde_json::from_str(&json).unwrap();
+    let second = restored.claim(1).unwrap();
+    assert_eq!(*second, vec![1; 3]);
+    drop(restored);
+    assert_eq!(*second, vec![1; 3]);
+
+    // Clearing drops them as well, and no handle is left to claim
+    let restored: VecArena<Vec<u32>> = serde_json::from_str(&json).unwrap();
+    unsafe { restored.clear() }
+    assert!(restored.claim(0).is_none());
+    assert_eq!(restored.stats().map(|stats| stats.occupied), Some(0));
+    restored.validate().unwrap();
+}
@@ -0,0 +1,138 @@
+extern crate spartacus;
+
+use std::cell::Cell;
+
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{TreeMap, Link, Shift, Pod, SnapshotError};
+use spartacus::tree::rule::{Noop, RevTreap};
+
+type ShiftMap = TreeMap<u32, i64, RevTreap, VecArena, Link<u32, i64, RevTreap, VecArena, Shift<i64>>>;
+
+const NIL: u64 = u64::MAX;
+
+/// Checksum described in the documentation of `tree::snapshot`.
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for chunk in data.chunks(8) {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash = (hash ^ u64::from_le_bytes(word)).wrapping_mul(0x100_0000_01b3);
+        hash ^= hash >> 32;
+    }
+
+    hash
+}
+
+/// Snapshot of `u32` keys and values, where each record takes the previous one as its left child.
+fn chain(len: u64, last_right: u64, valid: bool) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(b"SPTRMAP\0");
+    buf.extend_from_slice(&1u32.to_le_bytes());
+    buf.extend_from_slice(&4u32.to_le_bytes());
+    buf.extend_from_slice(&4u32.to_le_bytes());
+    buf.extend_from_slice(&0u32.to_le_bytes());
+    buf.extend_from_slice(&len.to_le_bytes());
+
+    for index in 0..len {
+        let left = if index == 0 { NIL } else { index - 1 };
+        let right = if index == len - 1 { last_right } else { NIL };
+        buf.extend_from_slice(&left.to_le_bytes());
+        buf.extend_from_slice(&right.to_le_bytes());
+        buf.extend_from_slice(&(index as u32).to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+    }
+
+    let checksum = if valid { checksum(&buf) } else { 0 };
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+#[test]
+fn pending_tags_are_written() {
+    let mut map: ShiftMap = (0..1000).map(|key| (key, 0)).collect();
+    map.update_range(100..900, 3);
+    map.update_range(500..600, -1);
+
+    let mut buf = Vec::new();
+    map.write_snapshot(&mut buf).unwrap();
+    let loaded = ShiftMap::read_snapshot(&buf[..]).unwrap();
+
+    loaded.validate().unwrap();
+    assert!(loaded.iter().eq(map.iter()));
+    assert_eq!(loaded[&550], 2);
+    assert_eq!(loaded[&950], 0);
+}
+
+#[test]
+fn deep_chain_is_freed_on_checksum_error() {
+    let buf = chain(300_000, NIL, false);
+
+    match TreeMap::<u32, u32, Noop, VecArena>::read_snapshot(&buf[..]) {
+        Err(SnapshotError::Checksum { .. }) => {}
+        _ => panic!("checksum should mismatch"),
+    }
+}
+
+#[test]
+fn deep_chain_is_freed_on_corrupt_record() {
+    // The right child of the root is taken as the left child already
+    let buf = chain(300_000, 299_998, true);
+
+    match TreeMap::<u32, u32, Noop, VecArena>::read_snapshot(&buf[..]) {
+        Err(SnapshotError::Corrupt { index: 299_999 }) => {}
+        _ => panic!("record should be corrupt"),
+    }
+}
+
+thread_local!(static DECODED: Cell<usize> = const { Cell::new(0) });
+
+/// Key which counts how many times it's decoded.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Counted(u32);
+
+impl Pod for Counted {
+    const SIZE: usize = 4;
+
+    fn encode(&self, buf: &mut [u8]) {
+        self.0.encode(buf);
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        DECODED.with(|decoded| decoded.set(decoded.get() + 1));
+        u32::decode(buf).map(Counted)
+    }
+}
+
+#[test]
+fn checksum_is_verified_before_building() {
+    let valid = chain(100, NIL, true);
+    let map = TreeMap::<Counted, u32, Noop, VecArena>::read_snapshot(&valid[..]).unwrap();
+    assert_eq!(map.len(), 100);
+    assert_eq!(map.first_key_value(), Some((&Counted(0), &0)));
+    assert_eq!(DECODED.with(Cell::get), 100);
+
+    // No record is decoded into a node
+    DECODED.with(|decoded| decoded.set(0));
+    let mut corrupt = valid.clone();
+    corrupt[40] ^= 1;
+    match TreeMap::<Counted, u32, Noop, VecArena>::read_snapshot(&corrupt[..]) {
+        Err(SnapshotError::Checksum { .. }) => {}
+        _ => panic!("checksum should mismatch"),
+    }
+    assert_eq!(DECODED.with(Cell::get), 0);
+
+    // A huge length fails when the source ends, not on allocation
+    let mut huge = valid.clone();
+    huge[24..32].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    match TreeMap::<Counted, u32, Noop, VecArena>::read_snapshot(&huge[..]) {
+        Err(SnapshotError::Truncated) => {}
+        _ => panic!("snapshot should be truncated"),
+    }
+
+    let truncated = &valid[..valid.len() - 1];
+    match TreeMap::<Counted, u32, Noop, VecArena>::read_snapshot(truncated) {
+        Err(SnapshotError::Truncated) => {}
+        _ => panic!("snapshot should be truncated"),
+    }
+}
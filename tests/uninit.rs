@@ -0,0 +1,127 @@
+extern crate spartacus;
+
+use std::cell::Cell;
+use std::mem::MaybeUninit;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+use spartacus::arena::{Arena, ArenaStats, BoxArena, Boxed as BoxedExt};
+use spartacus::arena::vec_arena::VecArena;
+
+struct Counted(Rc<Cell<usize>>, u32);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+/// Slots, occupied ones, free ones, and the rest which are taken by `Uninit`.
+fn stats<T>(arena: &VecArena<T>) -> (usize, usize, usize, usize) {
+    let ArenaStats { slots, occupied, free } = arena.stats().unwrap();
+    (slots, occupied, free, slots - occupied - free)
+}
+
+#[test]
+fn init_in_place() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+
+    let mut uninit = arena.alloc_uninit();
+    assert_eq!(uninit.slot(), 0);
+    assert_eq!(stats(&arena), (1, 0, 0, 1));
+    arena.validate().unwrap();
+
+    *uninit = MaybeUninit::new(Counted(drops.clone(), 1));
+    let boxed = unsafe { uninit.assume_init() };
+    assert_eq!(boxed.1, 1);
+    assert_eq!(stats(&arena), (1, 1, 0, 0));
+
+    let other = arena.alloc_uninit().init(Counted(drops.clone(), 2));
+    assert_eq!(BoxedExt::slot(&other), Some(1));
+    assert_eq!(other.1, 2);
+    arena.validate().unwrap();
+
+    drop((boxed, other));
+    assert_eq!(drops.get(), 2);
+    assert_eq!(stats(&arena), (2, 0, 2, 0));
+}
+
+#[test]
+fn uninit_dropped() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+    let kept = arena.alloc(Counted(drops.clone(), 0));
+
+    let uninits: Vec<_> = (0..3).map(|_| arena.alloc_uninit()).collect();
+    assert_eq!(stats(&arena), (4, 1, 0, 3));
+
+    // Nothing is dropped, as nothing is written
+    drop(uninits);
+    assert_eq!(drops.get(), 0);
+    assert_eq!(stats(&arena), (4, 1, 3, 0));
+    arena.validate().unwrap();
+
+    // Freed slots are reused, the last freed first
+    let reused = arena.alloc(Counted(drops.clone(), 1));
+    assert_eq!(BoxedExt::slot(&reused), Some(3));
+
+    drop((kept, reused));
+    assert_eq!(drops.get(), 2);
+}
+
+#[test]
+fn alloc_with_nested() {
+    let arena = VecArena::new();
+
+    // `f` may allocate in the same arena, which is not borrowed while it runs
+    let outer = arena.alloc_with(|| {
+        let inner = arena.alloc_with(|| 1);
+        *inner + 1
+    });
+
+    assert_eq!(*outer, 2);
+    assert_eq!(BoxedExt::slot(&outer), Some(0));
+    assert_eq!(stats(&arena), (2, 1, 1, 0));
+    arena.validate().unwrap();
+}
+
+#[test]
+fn alloc_with_panic() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = VecArena::new();
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        arena.alloc_with(|| -> Counted { panic!("no value") })
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(stats(&arena), (1, 0, 1, 0));
+    arena.validate().unwrap();
+
+    let boxed = arena.alloc_with(|| Counted(drops.clone(), 3));
+    assert_eq!((BoxedExt::slot(&boxed), boxed.1), (Some(0), 3));
+    drop(boxed);
+    assert_eq!(drops.get(), 1);
+}
+
+#[test]
+fn box_arena() {
+    let drops = Rc::new(Cell::new(0));
+    let arena = BoxArena::default();
+
+    let mut uninit = arena.alloc_uninit();
+    uninit.write(Counted(drops.clone(), 4));
+    let boxed = unsafe { uninit.assume_init() };
+    assert_eq!(boxed.1, 4);
+
+    // Dropped without writing, so nothing is dropped
+    drop(arena.alloc_uninit());
+    assert_eq!(drops.get(), 0);
+
+    let other = arena.alloc_with(|| Counted(drops.clone(), 5));
+    assert_eq!(other.1, 5);
+
+    drop((boxed, other));
+    assert_eq!(drops.get(), 2);
+}
@@ -0,0 +1,209 @@
+extern crate rand;
+extern crate spartacus;
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spartacus::arena::{ArenaFamily, BoxArena};
+use spartacus::arena::vec_arena::VecArena;
+use spartacus::tree::{TreeMap, TreeSeq, TreeMultiMap, Node, Link, Flip};
+use spartacus::tree::rule::{Rule, Noop, RevTreap};
+
+const KEYS: u32 = 200;
+
+fn rng() -> XorShiftRng {
+    XorShiftRng::from_seed([1, 2, 3, 4])
+}
+
+fn random_map<R, A>(rng: &mut XorShiftRng) -> (TreeMap<u32, u32, R, A>, BTreeMap<u32, u32>) where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<u32, u32, R, Link<u32, u32, R, A>>>: Default,
+{
+    let mut map = TreeMap::new();
+    let mut model = BTreeMap::new();
+
+    for _ in 0..rng.gen_range(0, 30) {
+        let (key, value) = (rng.gen_range(0, KEYS), rng.gen());
+        map.insert(key, value);
+        model.insert(key, value);
+    }
+
+    (map, model)
+}
+
+/// Every operation should keep the tree valid, including `up` links.
+fn map_operations<R, A>() where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<u32, u32, R, Link<u32, u32, R, A>>>: Default,
+{
+    let mut rng = rng();
+    let mut map: TreeMap<u32, u32, R, A> = TreeMap::new();
+    let mut model = BTreeMap::new();
+
+    for _ in 0..2000 {
+        let key = rng.gen_range(0, KEYS);
+
+        match rng.gen_range(0, 12) {
+            0..=2 => {
+                let value = rng.gen();
+                assert_eq!(map.insert(key, value), model.insert(key, value));
+            }
+            3..=4 => assert_eq!(map.remove(&key), model.remove(&key)),
+            5 => {
+                let first = model.keys().next().cloned();
+                assert_eq!(map.pop_first(), first.map(|key| (key, model.remove(&key).unwrap())));
+            }
+            6 => {
+                // Insert through a cursor at the previous key, or at the ghost position
+                if model.contains_key(&key) {
+                    continue;
+                }
+
+                let mut cursor = map.cursor_front_mut();
+                cursor.move_prev();
+                for _ in model.range(..key) {
+                    cursor.move_next();
+                }
+
+                cursor.insert_after(key, 0);
+                model.insert(key, 0);
+            }
+            7 => {
+                let index = rng.gen_range(0, model.len() + 1);
+                let mut cursor = map.cursor_back_mut();
+                for _ in 0..index {
+                    cursor.move_prev();
+                }
+
+                let expected = model.iter().rev().map(|(&k, &v)| (k, v)).nth(index);
+                assert_eq!(cursor.remove_current(), expected);
+                if let Some((key, _)) = expected {
+                    model.remove(&key);
+                }
+            }
+            8 => {
+                let (other, other_model) = random_map(&mut rng);
+                map.union_with(other, |_, value, other| *value ^= other);
+                for (key, value) in other_model {
+                    *model.entry(key).or_insert(0) ^= value;
+                }
+            }
+            9 => {
+                let (other, other_model) = random_map(&mut rng);
+                map.difference(other);
+                model.retain(|key, _| !other_model.contains_key(key));
+            }
+            10 => {
+                let (mut other, mut other_model) = random_map(&mut rng);
+                // Keep the map from shrinking too fast
+                for (&key, &value) in &model {
+                    if key % 2 == 0 {
+                        other.insert(key, value);
+                        other_model.insert(key, value);
+                    }
+                }
+
+                map.intersection_with(other, |_, _, _| {});
+                model.retain(|key, _| other_model.contains_key(key));
+            }
+            _ => {
+                let extracted: Vec<_> = map.extract_if(|key, _| key % 7 == 0).collect();
+                let expected: Vec<_> = model.iter().filter(|&(key, _)| key % 7 == 0).map(|(&k, &v)| (k, v)).collect();
+                assert_eq!(extracted, expected);
+                model.retain(|key, _| key % 7 != 0);
+            }
+        }
+
+        map.validate().unwrap();
+        assert!(map.iter().eq(model.iter()));
+    }
+}
+
+#[test]
+fn map_stays_valid() {
+    map_operations::<RevTreap, BoxArena>();
+    map_operations::<RevTreap, VecArena>();
+    map_operations::<Noop, BoxArena>();
+    map_operations::<Noop, VecArena>();
+}
+
+/// Splits and joins of a sequence should keep the tree valid.
+fn seq_operations<R, A>() where
+    R: Rule,
+    A: ArenaFamily,
+    A::Arena<Node<(), u32, R, Link<(), u32, R, A, Flip>>>: Default + Clone,
+{
+    let mut rng = rng();
+    let mut seq: TreeSeq<u32, R, A> = TreeSeq::new();
+    let mut model = Vec::new();
+
+    for _ in 0..2000 {
+        let index = rng.gen_range(0, model.len() + 1);
+
+        match rng.gen_range(0, 6) {
+            0..=1 => {
+                let value = rng.gen();
+                seq.insert_at(index, value);
+                model.insert(index, value);
+            }
+            2 => {
+                let expected = if index < model.len() { Some(model.remove(index)) } else { None };
+                assert_eq!(seq.remove_at(index), expected);
+            }
+            3 => {
+                let mut rest = seq.split_at(index);
+                let model_rest = model.split_off(index);
+                seq.validate().unwrap();
+                rest.validate().unwrap();
+                assert_eq!(rest.len(), model_rest.len());
+
+                rest.concat(seq);
+                seq = rest;
+                model = model_rest.into_iter().chain(model).collect();
+            }
+            _ => {
+                let end = rng.gen_range(index, model.len() + 1);
+                seq.reverse(index..end);
+                model[index..end].reverse();
+            }
+        }
+
+        seq.validate().unwrap();
+        assert_eq!(seq.len(), model.len());
+        for (index, value) in model.iter().enumerate() {
+            assert_eq!(seq.get(index), Some(value));
+        }
+    }
+}
+
+#[test]
+fn seq_stays_valid() {
+    seq_operations::<RevTreap, BoxArena>();
+    seq_operations::<RevTreap, VecArena>();
+    seq_operations::<Noop, VecArena>();
+}
+
+#[test]
+fn multimap_stays_valid() {
+    let mut rng = rng();
+    let mut map: TreeMultiMap<u32, u32, RevTreap, VecArena> = TreeMultiMap::new();
+    let mut len = 0;
+
+    for _ in 0..2000 {
+        let key = rng.gen_range(0, 50);
+
+        match rng.gen_range(0, 4) {
+            0..=1 => {
+                map.insert(key, rng.gen());
+                len += 1;
+            }
+            2 => len -= map.remove_one(&key).map_or(0, |_| 1),
+            _ => len -= map.remove_all(&key).len(),
+        }
+
+        map.validate().unwrap();
+        assert_eq!(map.len(), len);
+    }
+}